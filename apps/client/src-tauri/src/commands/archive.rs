@@ -0,0 +1,561 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use chrono::{DateTime, Datelike, Local, Timelike};
+use flate2::write::DeflateEncoder;
+use futures_util::{stream, Stream, StreamExt};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tokio_util::io::ReaderStream;
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const DESCRIPTOR_SIG: u32 = 0x0807_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const ZIP64_END_SIG: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIG: u32 = 0x0706_4b50;
+const END_SIG: u32 = 0x0605_4b50;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+// bit 3: crc e tamanhos vêm no descritor depois dos dados; bit 11: nomes em UTF-8
+const FLAGS: u16 = 0x0008 | 0x0800;
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+const LIMIT_32: u64 = 0xFFFF_FFFF;
+// deflate pode crescer um pouco em dados incompressíveis; folga para decidir
+// o ZIP64 antes de saber o tamanho comprimido
+const DEFLATE_MARGIN: u64 = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ZipMethod {
+    Store,
+    Deflate,
+}
+
+impl ZipMethod {
+    fn code(self) -> u16 {
+        match self {
+            ZipMethod::Store => 0,
+            ZipMethod::Deflate => 8,
+        }
+    }
+}
+
+/// Arquivo do disco que entra no ZIP com o nome `name` (separador `/`).
+#[derive(Debug, Clone)]
+pub(super) struct ZipSource {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// Pedaço do ZIP e quanto da origem de qual entrada ele leva.
+struct Piece {
+    bytes: Bytes,
+    source: Option<(usize, u64)>,
+}
+
+struct PlannedEntry {
+    source: ZipSource,
+    offset: u64,
+    zip64: bool,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+/// ZIP montado em streaming, sem arquivo temporário. O layout é decidido de
+/// antemão a partir dos tamanhos; no modo store isso dá o tamanho exato.
+pub(super) struct ZipPlan {
+    method: ZipMethod,
+    entries: Vec<PlannedEntry>,
+}
+
+impl ZipPlan {
+    pub(super) fn new(sources: Vec<ZipSource>, method: ZipMethod) -> Self {
+        let mut offset = 0u64;
+        let entries = sources
+            .into_iter()
+            .map(|source| {
+                let bound = match method {
+                    ZipMethod::Store => source.size,
+                    ZipMethod::Deflate => source.size.saturating_add(DEFLATE_MARGIN),
+                };
+                let zip64 = bound >= LIMIT_32;
+                let (dos_time, dos_date) = dos_timestamp(source.modified);
+                let entry = PlannedEntry {
+                    offset,
+                    zip64,
+                    dos_time,
+                    dos_date,
+                    source,
+                };
+                offset += entry.local_header_len() + entry.descriptor_len();
+                if method == ZipMethod::Store {
+                    offset += entry.source.size;
+                }
+                entry
+            })
+            .collect();
+        Self { method, entries }
+    }
+
+    /// Tamanho total da resposta; só conhecido sem compressão.
+    pub(super) fn content_length(&self) -> Option<u64> {
+        if self.method != ZipMethod::Store {
+            return None;
+        }
+        let data: u64 = self
+            .entries
+            .iter()
+            .map(|entry| entry.local_header_len() + entry.source.size + entry.descriptor_len())
+            .sum();
+        let central: u64 = self
+            .entries
+            .iter()
+            .map(|entry| entry.central_header_len(entry.offset))
+            .sum();
+        Some(data + central + end_len(self.entries.len(), data, central))
+    }
+
+    /// Lê os arquivos em ordem e produz os bytes do ZIP. Um arquivo que
+    /// mudou de tamanho no meio interrompe o stream com erro. `served(entrada,
+    /// bytes)` diz quanto da origem de cada entrada já saiu, à medida que o
+    /// stream é consumido.
+    pub(super) fn into_stream<F>(
+        self,
+        mut served: F,
+    ) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static
+    where
+        F: FnMut(usize, u64) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(8);
+        tokio::spawn(async move {
+            if let Err(error) = self.write_all(&tx).await {
+                let _ = tx.send(Err(error)).await;
+            }
+        });
+        stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|item| (item, rx))
+        })
+        .map(move |item| {
+            item.map(|piece| {
+                if let Some((entry, bytes)) = piece.source {
+                    served(entry, bytes);
+                }
+                piece.bytes
+            })
+        })
+    }
+
+    async fn write_all(self, tx: &mpsc::Sender<io::Result<Piece>>) -> io::Result<()> {
+        let send = |bytes: Bytes| async move {
+            tx.send(Ok(Piece {
+                bytes,
+                source: None,
+            }))
+            .await
+            .map_err(disconnected)
+        };
+
+        let mut written = Vec::with_capacity(self.entries.len());
+        let mut offset = 0u64;
+        for (index, entry) in self.entries.iter().enumerate() {
+            send(entry.local_header(self.method)).await?;
+            let (crc, compressed) = self.copy_data(index, entry, tx).await?;
+            let descriptor = entry.descriptor(crc, compressed, entry.source.size);
+            send(descriptor).await?;
+            written.push((offset, crc, compressed));
+            offset += entry.local_header_len() + compressed + entry.descriptor_len();
+        }
+
+        let central_start = offset;
+        let mut central = BytesMut::new();
+        for (entry, (offset, crc, compressed)) in self.entries.iter().zip(&written) {
+            entry.write_central_header(&mut central, self.method, *offset, *crc, *compressed);
+        }
+        let central_len = central.len() as u64;
+        write_end(&mut central, self.entries.len(), central_start, central_len);
+        send(central.freeze()).await
+    }
+
+    async fn copy_data(
+        &self,
+        index: usize,
+        entry: &PlannedEntry,
+        tx: &mpsc::Sender<io::Result<Piece>>,
+    ) -> io::Result<(u32, u64)> {
+        let file = File::open(&entry.source.path).await?;
+        // `take` + contagem: quem cresceu ou encolheu depois do plano é erro
+        let mut blocks = ReaderStream::new(file.take(entry.source.size));
+        let mut hasher = crc32fast::Hasher::new();
+        let mut read = 0u64;
+        // lido da origem e ainda não mandado (o deflate segura um pouco)
+        let mut unsent = 0u64;
+        let mut compressed = 0u64;
+        let mut encoder = match self.method {
+            ZipMethod::Store => None,
+            ZipMethod::Deflate => {
+                Some(DeflateEncoder::new(Vec::new(), flate2::Compression::fast()))
+            }
+        };
+        while let Some(block) = blocks.next().await {
+            let block = block?;
+            hasher.update(&block);
+            read += block.len() as u64;
+            unsent += block.len() as u64;
+            let out = match encoder.as_mut() {
+                Some(encoder) => {
+                    encoder.write_all(&block)?;
+                    Bytes::from(std::mem::take(encoder.get_mut()))
+                }
+                None => block,
+            };
+            if out.is_empty() {
+                continue;
+            }
+            compressed += out.len() as u64;
+            let piece = Piece {
+                bytes: out,
+                source: Some((index, std::mem::take(&mut unsent))),
+            };
+            tx.send(Ok(piece)).await.map_err(disconnected)?;
+        }
+        if read != entry.source.size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "origem modificada durante o envio: {}",
+                    entry.source.path.display()
+                ),
+            ));
+        }
+        if let Some(encoder) = encoder {
+            let tail = encoder.finish()?;
+            if !tail.is_empty() || unsent > 0 {
+                compressed += tail.len() as u64;
+                let piece = Piece {
+                    bytes: Bytes::from(tail),
+                    source: Some((index, unsent)),
+                };
+                tx.send(Ok(piece)).await.map_err(disconnected)?;
+            }
+        }
+        if !entry.zip64 && compressed >= LIMIT_32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "dados comprimidos excederam o limite do ZIP sem ZIP64",
+            ));
+        }
+        Ok((hasher.finalize(), compressed))
+    }
+}
+
+impl PlannedEntry {
+    fn name(&self) -> &[u8] {
+        self.source.name.as_bytes()
+    }
+
+    fn version(&self) -> u16 {
+        if self.zip64 {
+            VERSION_ZIP64
+        } else {
+            VERSION_DEFAULT
+        }
+    }
+
+    fn local_header_len(&self) -> u64 {
+        let extra = if self.zip64 { 4 + 16 } else { 0 };
+        30 + self.name().len() as u64 + extra
+    }
+
+    fn descriptor_len(&self) -> u64 {
+        if self.zip64 {
+            24
+        } else {
+            16
+        }
+    }
+
+    fn central_extra_len(&self, offset: u64) -> u64 {
+        let mut fields = 0;
+        if self.zip64 {
+            fields += 16;
+        }
+        if offset >= LIMIT_32 {
+            fields += 8;
+        }
+        if fields == 0 {
+            0
+        } else {
+            4 + fields
+        }
+    }
+
+    fn central_header_len(&self, offset: u64) -> u64 {
+        46 + self.name().len() as u64 + self.central_extra_len(offset)
+    }
+
+    fn local_header(&self, method: ZipMethod) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.local_header_len() as usize);
+        buf.put_u32_le(LOCAL_HEADER_SIG);
+        buf.put_u16_le(self.version());
+        buf.put_u16_le(FLAGS);
+        buf.put_u16_le(method.code());
+        buf.put_u16_le(self.dos_time);
+        buf.put_u16_le(self.dos_date);
+        buf.put_u32_le(0);
+        let sizes = if self.zip64 { u32::MAX } else { 0 };
+        buf.put_u32_le(sizes);
+        buf.put_u32_le(sizes);
+        buf.put_u16_le(self.name().len() as u16);
+        buf.put_u16_le(if self.zip64 { 20 } else { 0 });
+        buf.put_slice(self.name());
+        if self.zip64 {
+            buf.put_u16_le(ZIP64_EXTRA_ID);
+            buf.put_u16_le(16);
+            buf.put_u64_le(0);
+            buf.put_u64_le(0);
+        }
+        buf.freeze()
+    }
+
+    fn descriptor(&self, crc: u32, compressed: u64, size: u64) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.descriptor_len() as usize);
+        buf.put_u32_le(DESCRIPTOR_SIG);
+        buf.put_u32_le(crc);
+        if self.zip64 {
+            buf.put_u64_le(compressed);
+            buf.put_u64_le(size);
+        } else {
+            buf.put_u32_le(compressed as u32);
+            buf.put_u32_le(size as u32);
+        }
+        buf.freeze()
+    }
+
+    fn write_central_header(
+        &self,
+        buf: &mut BytesMut,
+        method: ZipMethod,
+        offset: u64,
+        crc: u32,
+        compressed: u64,
+    ) {
+        let big_offset = offset >= LIMIT_32;
+        let version = if self.zip64 || big_offset {
+            VERSION_ZIP64
+        } else {
+            VERSION_DEFAULT
+        };
+        buf.put_u32_le(CENTRAL_HEADER_SIG);
+        buf.put_u16_le(version);
+        buf.put_u16_le(version);
+        buf.put_u16_le(FLAGS);
+        buf.put_u16_le(method.code());
+        buf.put_u16_le(self.dos_time);
+        buf.put_u16_le(self.dos_date);
+        buf.put_u32_le(crc);
+        if self.zip64 {
+            buf.put_u32_le(u32::MAX);
+            buf.put_u32_le(u32::MAX);
+        } else {
+            buf.put_u32_le(compressed as u32);
+            buf.put_u32_le(self.source.size as u32);
+        }
+        buf.put_u16_le(self.name().len() as u16);
+        buf.put_u16_le(self.central_extra_len(offset) as u16);
+        buf.put_u16_le(0);
+        buf.put_u16_le(0);
+        buf.put_u16_le(0);
+        buf.put_u32_le(0);
+        buf.put_u32_le(if big_offset { u32::MAX } else { offset as u32 });
+        buf.put_slice(self.name());
+        if self.central_extra_len(offset) > 0 {
+            buf.put_u16_le(ZIP64_EXTRA_ID);
+            buf.put_u16_le((self.central_extra_len(offset) - 4) as u16);
+            // ordem fixa da especificação: original, comprimido, deslocamento
+            if self.zip64 {
+                buf.put_u64_le(self.source.size);
+                buf.put_u64_le(compressed);
+            }
+            if big_offset {
+                buf.put_u64_le(offset);
+            }
+        }
+    }
+}
+
+fn disconnected<T>(_: mpsc::error::SendError<T>) -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "cliente desconectado")
+}
+
+fn needs_zip64_end(count: usize, central_start: u64, central_len: u64) -> bool {
+    count >= 0xFFFF || central_start >= LIMIT_32 || central_len >= LIMIT_32
+}
+
+fn end_len(count: usize, central_start: u64, central_len: u64) -> u64 {
+    if needs_zip64_end(count, central_start, central_len) {
+        56 + 20 + 22
+    } else {
+        22
+    }
+}
+
+fn write_end(buf: &mut BytesMut, count: usize, central_start: u64, central_len: u64) {
+    if needs_zip64_end(count, central_start, central_len) {
+        let zip64_end = central_start + central_len;
+        buf.put_u32_le(ZIP64_END_SIG);
+        buf.put_u64_le(44);
+        buf.put_u16_le(VERSION_ZIP64);
+        buf.put_u16_le(VERSION_ZIP64);
+        buf.put_u32_le(0);
+        buf.put_u32_le(0);
+        buf.put_u64_le(count as u64);
+        buf.put_u64_le(count as u64);
+        buf.put_u64_le(central_len);
+        buf.put_u64_le(central_start);
+
+        buf.put_u32_le(ZIP64_LOCATOR_SIG);
+        buf.put_u32_le(0);
+        buf.put_u64_le(zip64_end);
+        buf.put_u32_le(1);
+    }
+    buf.put_u32_le(END_SIG);
+    buf.put_u16_le(0);
+    buf.put_u16_le(0);
+    let short_count = count.min(0xFFFF) as u16;
+    buf.put_u16_le(short_count);
+    buf.put_u16_le(short_count);
+    buf.put_u32_le(central_len.min(LIMIT_32) as u32);
+    buf.put_u32_le(central_start.min(LIMIT_32) as u32);
+    buf.put_u16_le(0);
+}
+
+/// Data e hora no formato do MS-DOS, no fuso local; antes de 1980 vira 1980.
+fn dos_timestamp(modified: Option<SystemTime>) -> (u16, u16) {
+    let Some(modified) = modified else {
+        return (0, (1 << 5) | 1);
+    };
+    let local: DateTime<Local> = modified.into();
+    if local.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = (local.hour() << 11) | (local.minute() << 5) | (local.second() / 2);
+    let date = (((local.year() - 1980) as u32).min(127) << 9) | (local.month() << 5) | local.day();
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::io::Read;
+    use std::sync::Arc;
+
+    fn read_u16(data: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([data[at], data[at + 1]])
+    }
+
+    fn read_u32(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    // bytes do ZIP e quanto da origem de cada entrada foi avisado como enviado
+    async fn collect(plan: ZipPlan) -> (Vec<u8>, Vec<u64>) {
+        let served = Arc::new(Mutex::new(vec![0u64; plan.entries.len()]));
+        let counter = served.clone();
+        let mut stream =
+            Box::pin(plan.into_stream(move |entry, bytes| counter.lock()[entry] += bytes));
+        let mut out = Vec::new();
+        while let Some(block) = stream.next().await {
+            out.extend_from_slice(&block.unwrap());
+        }
+        let served = served.lock().clone();
+        (out, served)
+    }
+
+    #[test]
+    fn store_length_matches_and_deflate_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let contents: [(&str, Vec<u8>); 2] = [
+            ("a.txt", b"ola mundo ".repeat(5000)),
+            (
+                "pasta/b.bin",
+                (0..70_000u32).map(|i| (i * 31) as u8).collect(),
+            ),
+        ];
+        let sources: Vec<ZipSource> = contents
+            .iter()
+            .enumerate()
+            .map(|(i, (name, data))| {
+                let path = dir.path().join(format!("{i}.dat"));
+                std::fs::write(&path, data).unwrap();
+                ZipSource {
+                    name: name.to_string(),
+                    path,
+                    size: data.len() as u64,
+                    modified: Some(SystemTime::now()),
+                }
+            })
+            .collect();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let store = ZipPlan::new(sources.clone(), ZipMethod::Store);
+        let expected = store.content_length().unwrap();
+        let sizes: Vec<u64> = contents.iter().map(|(_, data)| data.len() as u64).collect();
+        let (zip, served) = rt.block_on(collect(store));
+        assert_eq!(served, sizes);
+        assert_eq!(zip.len() as u64, expected);
+        assert_eq!(read_u32(&zip, 0), LOCAL_HEADER_SIG);
+        let end = zip.len() - 22;
+        assert_eq!(read_u32(&zip, end), END_SIG);
+        assert_eq!(read_u16(&zip, end + 10), 2);
+
+        let deflate = ZipPlan::new(sources, ZipMethod::Deflate);
+        assert!(deflate.content_length().is_none());
+        let (zip, served) = rt.block_on(collect(deflate));
+        assert_eq!(served, sizes);
+        // primeira entrada: dados entre o cabeçalho local e o descritor
+        let name_len = read_u16(&zip, 26) as usize;
+        let data_start = 30 + name_len;
+        let central_start = read_u32(&zip, zip.len() - 6) as usize;
+        let central_compressed = read_u32(&zip, central_start + 20) as usize;
+        let central_crc = read_u32(&zip, central_start + 16);
+        let mut inflated = Vec::new();
+        flate2::read::DeflateDecoder::new(&zip[data_start..data_start + central_compressed])
+            .read_to_end(&mut inflated)
+            .unwrap();
+        assert_eq!(inflated, contents[0].1);
+        assert_eq!(central_crc, crc32fast::hash(&contents[0].1));
+    }
+
+    #[test]
+    fn large_entries_switch_to_zip64() {
+        let source = |size| ZipSource {
+            name: "grande.iso".to_string(),
+            path: PathBuf::from("grande.iso"),
+            size,
+            modified: None,
+        };
+        let small = ZipPlan::new(vec![source(10)], ZipMethod::Store);
+        let large = ZipPlan::new(vec![source(5 << 30), source(10)], ZipMethod::Store);
+        assert!(!small.entries[0].zip64);
+        assert!(large.entries[0].zip64 && !large.entries[1].zip64);
+        // a segunda entrada começa depois de 4 GiB: deslocamento vai no extra
+        assert!(large.entries[1].offset >= LIMIT_32);
+        let header = |entry: &PlannedEntry| entry.local_header_len() + entry.descriptor_len();
+        let expected = header(&large.entries[0])
+            + (5 << 30)
+            + header(&large.entries[1])
+            + 10
+            + large.entries[0].central_header_len(0)
+            + large.entries[1].central_header_len(large.entries[1].offset)
+            + 56
+            + 20
+            + 22;
+        assert_eq!(large.content_length(), Some(expected));
+    }
+}
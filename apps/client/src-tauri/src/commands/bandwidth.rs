@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use super::settings::Settings;
+use super::transfer::TransferManager;
+
+/// Chave do limite por sessão aplicado aos downloads do host HTTP.
+pub(super) const HOST_SESSION: &str = "tunnel:host";
+
+/// Token bucket em bytes/s, com rajada de um segundo. Limite 0 = sem limite.
+/// O limite é lido a cada reserva, então mudá-lo vale para quem já está
+/// transferindo.
+pub struct RateLimiter {
+    limit: AtomicU64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit: AtomicU64::new(limit),
+            bucket: Mutex::new(Bucket {
+                tokens: limit as f64,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    pub fn set_limit(&self, limit: u64) {
+        let mut bucket = self.bucket.lock();
+        self.limit.store(limit, Ordering::Relaxed);
+        // dívida acumulada no limite antigo não pune o novo
+        bucket.tokens = bucket.tokens.clamp(0.0, limit as f64);
+        bucket.last = Instant::now();
+    }
+
+    /// Retira `bytes` do balde e devolve quanto esperar antes de usá-los. O
+    /// saldo pode ficar negativo: um chunk maior que a rajada espera a dívida.
+    fn reserve(&self, bytes: u64) -> Duration {
+        let limit = self.limit();
+        if limit == 0 {
+            return Duration::ZERO;
+        }
+        let rate = limit as f64;
+        let mut bucket = self.bucket.lock();
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.last).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refill).min(rate);
+        bucket.last = now;
+        bucket.tokens -= bytes as f64;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        }
+    }
+
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+struct SessionLimit {
+    limiter: Arc<RateLimiter>,
+    // definido pela UI para esta sessão; não segue o padrão das configurações
+    custom: bool,
+}
+
+/// Limites de envio: um balde global e um por sessão, todos ajustáveis em
+/// tempo de execução.
+#[derive(Clone)]
+pub struct BandwidthManager {
+    global: Arc<RateLimiter>,
+    session_default: Arc<AtomicU64>,
+    sessions: Arc<Mutex<HashMap<String, SessionLimit>>>,
+}
+
+impl Default for BandwidthManager {
+    fn default() -> Self {
+        Self {
+            global: Arc::new(RateLimiter::new(0)),
+            session_default: Arc::new(AtomicU64::new(0)),
+            sessions: Arc::default(),
+        }
+    }
+}
+
+impl BandwidthManager {
+    pub fn configure(&self, settings: &Settings) {
+        self.global.set_limit(settings.global_rate_limit);
+        self.session_default
+            .store(settings.session_rate_limit, Ordering::Relaxed);
+        for session in self.sessions.lock().values() {
+            if !session.custom {
+                session.limiter.set_limit(settings.session_rate_limit);
+            }
+        }
+    }
+
+    pub fn session(&self, session_id: &str) -> Arc<RateLimiter> {
+        self.sessions
+            .lock()
+            .entry(session_id.to_string())
+            .or_insert_with(|| SessionLimit {
+                limiter: Arc::new(RateLimiter::new(
+                    self.session_default.load(Ordering::Relaxed),
+                )),
+                custom: false,
+            })
+            .limiter
+            .clone()
+    }
+
+    /// `None` volta a sessão ao limite padrão das configurações. Só para
+    /// sessões em andamento: é o fim delas que chama `release`.
+    pub fn set_session_limit(&self, session_id: &str, limit: Option<u64>) {
+        let limiter = self.session(session_id);
+        let custom = limit.is_some();
+        limiter.set_limit(limit.unwrap_or_else(|| self.session_default.load(Ordering::Relaxed)));
+        if let Some(session) = self.sessions.lock().get_mut(session_id) {
+            session.custom = custom;
+        }
+    }
+
+    pub fn release(&self, session_id: &str) {
+        self.sessions.lock().remove(session_id);
+    }
+
+    /// Espera até `bytes` caberem no limite da sessão e no global.
+    pub async fn throttle(&self, session: &RateLimiter, bytes: u64) {
+        session.acquire(bytes).await;
+        self.global.acquire(bytes).await;
+    }
+}
+
+#[tauri::command]
+pub fn set_session_rate_limit(
+    bandwidth: tauri::State<'_, BandwidthManager>,
+    transfer_manager: tauri::State<'_, TransferManager>,
+    session_id: String,
+    bytes_per_sec: Option<u64>,
+) -> Result<(), String> {
+    if session_id != HOST_SESSION && !transfer_manager.is_sending(&session_id) {
+        return Err(format!("sessão desconhecida: {session_id}"));
+    }
+    bandwidth.set_session_limit(&session_id, bytes_per_sec);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_paces_to_the_current_limit() {
+        const MIB: u64 = 1024 * 1024;
+        // relógio pausado: o tokio avança o tempo sozinho a cada espera
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let limiter = RateLimiter::new(MIB);
+            let start = Instant::now();
+            // a rajada de 1 s sai na hora; o resto a 1 MiB/s
+            for _ in 0..24 {
+                limiter.acquire(64 * 1024).await;
+            }
+            assert_eq!(start.elapsed().as_millis(), 500);
+
+            // dobrar o limite no meio do caminho vale para os próximos bytes
+            limiter.set_limit(2 * MIB);
+            let start = Instant::now();
+            for _ in 0..16 {
+                limiter.acquire(64 * 1024).await;
+            }
+            assert_eq!(start.elapsed().as_millis(), 500);
+
+            limiter.set_limit(0);
+            let start = Instant::now();
+            limiter.acquire(u64::MAX / 2).await;
+            assert_eq!(start.elapsed(), Duration::ZERO);
+        });
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use fastcdc::v2020::{self as cdc, StreamCDC};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::outboard::{Outboard, OutboardBuilder};
+use super::transfer::ChunkInfo;
+use super::transport::PeerTarget;
+
+/// Como o arquivo é cortado em chunks. Escolhido por sessão.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChunkingMode {
+    /// Fatias de `chunk_size` bytes.
+    #[default]
+    Fixed,
+    /// Fronteiras definidas pelo conteúdo (FastCDC), com `chunk_size` como
+    /// tamanho médio: uma edição só muda os chunks ao redor dela.
+    ContentDefined,
+}
+
+/// Resultado da leitura inicial do arquivo: a árvore blake3 e os chunks.
+pub struct FilePlan {
+    pub outboard: Outboard,
+    pub chunks: Vec<ChunkInfo>,
+}
+
+pub fn plan_file(
+    reader: impl Read,
+    size: u64,
+    mode: ChunkingMode,
+    chunk_size: u64,
+) -> anyhow::Result<FilePlan> {
+    let mut outboard = OutboardBuilder::default();
+    let mut chunks = Vec::new();
+    let mut push = |data: &[u8]| {
+        outboard.update(data);
+        chunks.push(ChunkInfo {
+            index: chunks.len() as u64,
+            hash: blake3::hash(data).to_hex().to_string(),
+            size: data.len() as u64,
+            ..Default::default()
+        });
+    };
+
+    let mut reader = reader.take(size);
+    match mode {
+        ChunkingMode::Fixed => loop {
+            let mut buffer = Vec::with_capacity(chunk_size as usize);
+            (&mut reader).take(chunk_size).read_to_end(&mut buffer)?;
+            if buffer.is_empty() {
+                break;
+            }
+            push(&buffer);
+        },
+        ChunkingMode::ContentDefined => {
+            let (min, avg, max) = cdc_limits(chunk_size);
+            for chunk in StreamCDC::new(reader, min, avg, max) {
+                push(&chunk?.data);
+            }
+        }
+    }
+
+    let outboard = outboard.finish();
+    anyhow::ensure!(
+        outboard.size() == size,
+        "arquivo com {} bytes, esperado {size}",
+        outboard.size()
+    );
+    Ok(FilePlan { outboard, chunks })
+}
+
+// mínimo e máximo a 1/4 e 4x da média, dentro do que o FastCDC aceita
+fn cdc_limits(chunk_size: u64) -> (u32, u32, u32) {
+    let avg = chunk_size.clamp(cdc::AVERAGE_MIN as u64, cdc::AVERAGE_MAX as u64) as u32;
+    let min = (avg / 4).clamp(cdc::MINIMUM_MIN, cdc::MINIMUM_MAX);
+    let max = avg
+        .saturating_mul(4)
+        .clamp(cdc::MAXIMUM_MIN, cdc::MAXIMUM_MAX);
+    (min, avg, max)
+}
+
+/// Onde o conteúdo de um chunk já recebido está guardado.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkLocation {
+    pub path: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+// sessões paralelas acrescentam ao mesmo journal
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+// journal maior que isso é compactado no próximo registro
+const COMPACT_AFTER_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Um arquivo concluído, uma linha no journal do índice. O último registro
+/// de um caminho substitui os anteriores.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexRecord {
+    path: String,
+    chunks: Vec<IndexedChunk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedChunk {
+    hash: String,
+    offset: u64,
+    size: u64,
+}
+
+/// Índice hash → arquivo recebido, para um reenvio montar chunks iguais a
+/// partir do que já está em disco. Cada par origem + destino tem o seu: um
+/// peer não descobre nem copia o que outro mandou.
+pub struct ChunkIndex {
+    path: PathBuf,
+}
+
+impl ChunkIndex {
+    pub fn new(dir: &Path, peer: &PeerTarget, destination: &Path) -> Self {
+        let scope = format!(
+            "{}\n{}\n{}",
+            peer.route(),
+            peer.describe(),
+            destination.display()
+        );
+        let scope = blake3::hash(scope.as_bytes()).to_hex();
+        Self {
+            path: dir.join("chunks").join(format!("{}.journal", &scope[..32])),
+        }
+    }
+
+    pub fn load(&self) -> HashMap<String, ChunkLocation> {
+        let mut entries = HashMap::new();
+        for record in self.records() {
+            for chunk in record.chunks {
+                entries.insert(
+                    chunk.hash,
+                    ChunkLocation {
+                        path: record.path.clone(),
+                        offset: chunk.offset,
+                        size: chunk.size,
+                    },
+                );
+            }
+        }
+        entries
+    }
+
+    /// Registro vigente de cada caminho; linhas rasgadas no fim são ignoradas.
+    fn records(&self) -> Vec<IndexRecord> {
+        let data = fs::read(&self.path).unwrap_or_default();
+        let mut latest: HashMap<String, IndexRecord> = HashMap::new();
+        for line in data.split(|b| *b == b'\n') {
+            if let Ok(record) = serde_json::from_slice::<IndexRecord>(line) {
+                latest.insert(record.path.clone(), record);
+            }
+        }
+        latest.into_values().collect()
+    }
+
+    /// Registra os chunks de um arquivo concluído, esquecendo o que antes
+    /// apontava para o mesmo caminho.
+    pub fn record(&self, path: &Path, chunks: &[ChunkInfo]) -> anyhow::Result<()> {
+        let _guard = INDEX_LOCK.lock();
+        let mut chunks = chunks.to_vec();
+        chunks.sort_by_key(|c| c.index);
+        let mut offset = 0u64;
+        let record = IndexRecord {
+            path: path.to_string_lossy().to_string(),
+            chunks: chunks
+                .into_iter()
+                .map(|chunk| {
+                    let indexed = IndexedChunk {
+                        hash: chunk.hash,
+                        offset,
+                        size: chunk.size,
+                    };
+                    offset += chunk.size;
+                    indexed
+                })
+                .collect(),
+        };
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("abrir {}", self.path.display()))?;
+        // um único write: ou a linha entra inteira ou fica rasgada no fim
+        file.write_all(&line)?;
+        if file.metadata()?.len() > COMPACT_AFTER_BYTES {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    fn compact(&self) -> anyhow::Result<()> {
+        let mut data = Vec::new();
+        for record in self.records() {
+            data.extend(serde_json::to_vec(&record)?);
+            data.push(b'\n');
+        }
+        let temp = self.path.with_extension("journal.tmp");
+        let mut file = File::create(&temp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&temp, &self.path).with_context(|| format!("substituir {}", self.path.display()))
+    }
+}
+
+/// Lê o chunk de onde o índice aponta. Arquivos alterados ou apagados depois
+/// de indexados simplesmente não conferem.
+pub fn read_indexed(
+    entries: &HashMap<String, ChunkLocation>,
+    hash: &str,
+    size: u64,
+) -> Option<Vec<u8>> {
+    let location = entries.get(hash).filter(|l| l.size == size)?;
+    let mut file = File::open(&location.path).ok()?;
+    file.seek(SeekFrom::Start(location.offset)).ok()?;
+    let mut data = vec![0u8; size as usize];
+    file.read_exact(&mut data).ok()?;
+    (blake3::hash(&data).to_hex().as_str() == hash).then_some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn insertion_only_changes_nearby_chunks() {
+        let original = sample(1 << 20, 7);
+        let mut edited = original.clone();
+        edited.insert(1000, 0x42);
+
+        let plan = |data: &[u8], mode| {
+            plan_file(data, data.len() as u64, mode, 16 * 1024)
+                .unwrap()
+                .chunks
+        };
+        let shared = |mode| {
+            let before: Vec<String> = plan(&original, mode).into_iter().map(|c| c.hash).collect();
+            let after = plan(&edited, mode);
+            let reused = after.iter().filter(|c| before.contains(&c.hash)).count();
+            (reused, after.len())
+        };
+
+        let (reused, total) = shared(ChunkingMode::Fixed);
+        assert_eq!(reused, 0, "fixo: tudo depois da inserção muda");
+        let (reused, total_cdc) = shared(ChunkingMode::ContentDefined);
+        assert!(
+            reused + 2 >= total_cdc,
+            "cdc reaproveitou {reused} de {total_cdc}"
+        );
+        assert!(total > 1 && total_cdc > 1);
+
+        let plan = plan_file(
+            &edited[..],
+            edited.len() as u64,
+            ChunkingMode::ContentDefined,
+            16 * 1024,
+        )
+        .unwrap();
+        assert_eq!(
+            plan.outboard.root(),
+            blake3::hash(&edited).to_hex().as_str()
+        );
+        assert_eq!(
+            plan.chunks.iter().map(|c| c.size).sum::<u64>(),
+            edited.len() as u64
+        );
+    }
+
+    #[test]
+    fn index_finds_chunks_of_finished_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = sample(100_000, 3);
+        let file = dir.path().join("recebido.bin");
+        fs::write(&file, &data).unwrap();
+        let plan = plan_file(
+            &data[..],
+            data.len() as u64,
+            ChunkingMode::ContentDefined,
+            4096,
+        )
+        .unwrap();
+
+        let peer = PeerTarget::Quic {
+            remote_addr: "10.0.0.2:5000".into(),
+        };
+        let index = ChunkIndex::new(dir.path(), &peer, dir.path());
+        index.record(&file, &plan.chunks).unwrap();
+        // regravar o mesmo caminho substitui o registro anterior
+        index.record(&file, &plan.chunks).unwrap();
+        let entries = ChunkIndex::new(dir.path(), &peer, dir.path()).load();
+        assert_eq!(entries.len(), plan.chunks.len());
+        let last = plan.chunks.last().unwrap();
+        let found = read_indexed(&entries, &last.hash, last.size).unwrap();
+        assert!(data.ends_with(&found));
+
+        // o arquivo mudou depois de indexado: o hash não confere mais
+        fs::write(&file, sample(100_000, 4)).unwrap();
+        assert!(read_indexed(&entries, &last.hash, last.size).is_none());
+
+        // outro peer ou outro destino não enxerga o índice
+        let stranger = PeerTarget::Quic {
+            remote_addr: "10.0.0.3:5000".into(),
+        };
+        assert!(ChunkIndex::new(dir.path(), &stranger, dir.path())
+            .load()
+            .is_empty());
+        assert!(
+            ChunkIndex::new(dir.path(), &peer, &dir.path().join("outra"))
+                .load()
+                .is_empty()
+        );
+    }
+}
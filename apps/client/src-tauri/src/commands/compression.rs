@@ -0,0 +1,134 @@
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+// amostras espalhadas pelo chunk para estimar a entropia sem comprimir tudo
+const SAMPLE_LEN: usize = 4 * 1024;
+const SAMPLES: usize = 8;
+// acima disso (bits por byte) o conteúdo já está comprimido ou cifrado
+const ENTROPY_LIMIT: f64 = 7.5;
+
+/// Compressão escolhida para a sessão.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd {
+        level: i32,
+    },
+}
+
+/// Como um chunk viajou. Cada chunk decide sozinho: `Raw` também quando a
+/// compressão não compensou.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChunkEncoding {
+    #[default]
+    Raw,
+    Zstd,
+}
+
+/// Comprime `data` se a sessão pedir e o conteúdo parecer compressível.
+pub fn encode_chunk(
+    compression: Compression,
+    data: Vec<u8>,
+) -> anyhow::Result<(ChunkEncoding, Vec<u8>)> {
+    let Compression::Zstd { level } = compression else {
+        return Ok((ChunkEncoding::Raw, data));
+    };
+    if looks_compressed(&data) {
+        return Ok((ChunkEncoding::Raw, data));
+    }
+    let packed = zstd::bulk::compress(&data, level)?;
+    if packed.len() >= data.len() {
+        return Ok((ChunkEncoding::Raw, data));
+    }
+    Ok((ChunkEncoding::Zstd, packed))
+}
+
+/// Desfaz `encode_chunk`, recusando saída maior que `limit` bytes.
+pub fn decode_chunk(
+    encoding: ChunkEncoding,
+    data: Vec<u8>,
+    limit: usize,
+) -> anyhow::Result<Vec<u8>> {
+    match encoding {
+        ChunkEncoding::Raw => Ok(data),
+        ChunkEncoding::Zstd => {
+            let mut plain = Vec::new();
+            zstd::stream::read::Decoder::new(&data[..])?
+                .take(limit as u64 + 1)
+                .read_to_end(&mut plain)?;
+            anyhow::ensure!(plain.len() <= limit, "chunk descomprimido grande demais");
+            Ok(plain)
+        }
+    }
+}
+
+fn looks_compressed(data: &[u8]) -> bool {
+    if data.len() < SAMPLE_LEN {
+        return entropy(data) > ENTROPY_LIMIT;
+    }
+    let stride = (data.len() - SAMPLE_LEN) / (SAMPLES - 1);
+    let high = (0..SAMPLES)
+        .map(|i| &data[i * stride..i * stride + SAMPLE_LEN])
+        .filter(|sample| entropy(sample) > ENTROPY_LIMIT)
+        .count();
+    // maioria das amostras: um cabeçalho de texto num .zip não engana
+    high * 2 > SAMPLES
+}
+
+fn entropy(sample: &[u8]) -> f64 {
+    if sample.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for byte in sample {
+        counts[*byte as usize] += 1;
+    }
+    let len = sample.len() as f64;
+    counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_compressed_and_noise_is_not() {
+        let zstd = Compression::Zstd { level: 3 };
+        let text = "2024-05-01 INFO conexão aceita de 10.0.0.7\n"
+            .repeat(5000)
+            .into_bytes();
+        let (encoding, packed) = encode_chunk(zstd, text.clone()).unwrap();
+        assert_eq!(encoding, ChunkEncoding::Zstd);
+        assert!(packed.len() * 10 < text.len());
+        assert_eq!(
+            decode_chunk(encoding, packed.clone(), text.len()).unwrap(),
+            text
+        );
+        assert!(decode_chunk(encoding, packed, text.len() - 1).is_err());
+
+        // já comprimido: vai cru, sem gastar CPU com o zstd
+        let noise = zstd::bulk::compress(&text, 19)
+            .unwrap()
+            .into_iter()
+            .chain((0..200_000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8))
+            .collect::<Vec<u8>>();
+        assert!(looks_compressed(&noise));
+        let (encoding, raw) = encode_chunk(zstd, noise.clone()).unwrap();
+        assert_eq!(encoding, ChunkEncoding::Raw);
+        assert_eq!(raw, noise);
+
+        let (encoding, _) = encode_chunk(Compression::None, text).unwrap();
+        assert_eq!(encoding, ChunkEncoding::Raw);
+    }
+}
@@ -0,0 +1,217 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+const FILE_SUBKEY_CONTEXT: &str = "fluxshare 2024-06 chunk subkey v1";
+const KEY_CHECK_CONTEXT: &str = "fluxshare 2024-06 key check v1";
+const FILE_SALT_LEN: usize = 16;
+const SESSION_SALT_LEN: usize = 16;
+
+// limites para parâmetros vindos do remetente (evita DoS de memória/CPU no receptor)
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 64;
+const MAX_PARALLELISM: u32 = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfCost {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfCost {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfParams {
+    pub salt: String,
+    #[serde(flatten)]
+    pub cost: KdfCost,
+}
+
+impl KdfParams {
+    pub fn generate(cost: &KdfCost) -> Self {
+        Self {
+            salt: random_hex(SESSION_SALT_LEN),
+            cost: cost.clone(),
+        }
+    }
+
+    fn argon2(&self) -> anyhow::Result<Argon2<'static>> {
+        let KdfCost {
+            memory_kib,
+            iterations,
+            parallelism,
+        } = self.cost;
+        anyhow::ensure!(
+            memory_kib <= MAX_MEMORY_KIB
+                && iterations <= MAX_ITERATIONS
+                && parallelism <= MAX_PARALLELISM,
+            "parâmetros argon2 acima do limite"
+        );
+        let params = Params::new(memory_kib, iterations, parallelism, Some(32))
+            .map_err(|e| anyhow::anyhow!("parâmetros argon2 inválidos: {e}"))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+pub fn derive_key(password: &str, kdf: &KdfParams) -> anyhow::Result<[u8; 32]> {
+    let salt_bytes = decode_hex(&kdf.salt)
+        .filter(|salt| salt.len() == SESSION_SALT_LEN)
+        .ok_or_else(|| anyhow::anyhow!("salt de sessão inválido"))?;
+
+    let mut output = [0u8; 32];
+    kdf.argon2()?
+        .hash_password_into(password.as_bytes(), &salt_bytes, &mut output)
+        .map_err(|e| anyhow::anyhow!("argon2 derivation: {e}"))?;
+
+    Ok(output)
+}
+
+/// MAC do session id sob a chave derivada: o receptor confere a senha antes de
+/// aceitar qualquer chunk.
+pub fn key_check(session_key: &[u8; 32], session_id: &str) -> String {
+    key_check_hash(session_key, session_id).to_hex().to_string()
+}
+
+pub fn verify_key_check(session_key: &[u8; 32], session_id: &str, expected: &str) -> bool {
+    // a comparação de blake3::Hash é em tempo constante
+    blake3::Hash::from_hex(expected)
+        .map(|expected| expected == key_check_hash(session_key, session_id))
+        .unwrap_or(false)
+}
+
+fn key_check_hash(session_key: &[u8; 32], session_id: &str) -> blake3::Hash {
+    let mac_key = blake3::derive_key(KEY_CHECK_CONTEXT, session_key);
+    blake3::keyed_hash(&mac_key, session_id.as_bytes())
+}
+
+/// Salt aleatório por arquivo; cada execução da sessão sorteia outro, então um
+/// mesmo (subchave, índice) nunca cifra dois conteúdos diferentes.
+pub fn random_file_salt() -> String {
+    random_hex(FILE_SALT_LEN)
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+pub fn file_key(session_key: &[u8; 32], file_salt: &str) -> anyhow::Result<[u8; 32]> {
+    anyhow::ensure!(
+        file_salt.len() == FILE_SALT_LEN * 2 && file_salt.chars().all(|c| c.is_ascii_hexdigit()),
+        "salt de arquivo inválido"
+    );
+    let mut material = Vec::with_capacity(32 + file_salt.len());
+    material.extend_from_slice(session_key);
+    material.extend_from_slice(file_salt.as_bytes());
+    Ok(blake3::derive_key(FILE_SUBKEY_CONTEXT, &material))
+}
+
+fn chunk_nonce(chunk_index: u64) -> Nonce {
+    let mut nonce_bytes = [0u8; 12];
+    nonce_bytes[..8].copy_from_slice(&chunk_index.to_be_bytes());
+    nonce_bytes.into()
+}
+
+pub fn encrypt_chunk(
+    file_key: &[u8; 32],
+    chunk_index: u64,
+    plain: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    ChaCha20Poly1305::new(Key::from_slice(file_key))
+        .encrypt(&chunk_nonce(chunk_index), plain)
+        .map_err(|_| anyhow::anyhow!("encrypt chunk"))
+}
+
+pub fn decrypt_chunk(
+    file_key: &[u8; 32],
+    chunk_index: u64,
+    sealed: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    ChaCha20Poly1305::new(Key::from_slice(file_key))
+        .decrypt(&chunk_nonce(chunk_index), sealed)
+        .map_err(|_| anyhow::anyhow!("decrypt chunk {chunk_index}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn key_nonce_pairs_never_repeat_across_files() {
+        let session_key = [7u8; 32];
+        let mut seen = HashSet::new();
+        for _ in 0..16 {
+            let key = file_key(&session_key, &random_file_salt()).unwrap();
+            for index in 0..64u64 {
+                let nonce = chunk_nonce(index);
+                assert!(
+                    seen.insert((key, nonce.to_vec())),
+                    "par (chave, nonce) repetido"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn session_salt_changes_the_key() {
+        let cheap = KdfCost {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let a = KdfParams::generate(&cheap);
+        let b = KdfParams::generate(&cheap);
+        assert_ne!(a.salt, b.salt);
+        assert_ne!(
+            derive_key("senha", &a).unwrap(),
+            derive_key("senha", &b).unwrap()
+        );
+        assert_eq!(
+            derive_key("senha", &a).unwrap(),
+            derive_key("senha", &a.clone()).unwrap()
+        );
+
+        let greedy = KdfParams {
+            cost: KdfCost {
+                memory_kib: MAX_MEMORY_KIB + 1,
+                ..cheap
+            },
+            ..a
+        };
+        assert!(derive_key("senha", &greedy).is_err());
+    }
+
+    #[test]
+    fn file_key_depends_on_salt() {
+        let session_key = [1u8; 32];
+        let a = file_key(&session_key, "00112233445566778899aabbccddeeff").unwrap();
+        let b = file_key(&session_key, "00112233445566778899aabbccddeefe").unwrap();
+        assert_ne!(a, b);
+        assert!(file_key(&session_key, "curto").is_err());
+    }
+}
@@ -1,33 +1,371 @@
+use std::collections::HashSet;
 use std::fs::{self, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use super::transfer::FileEntry;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::transfer::{FileEntry, FileMetadata};
+
+/// O que fazer com links simbólicos ao expandir uma seleção.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SymlinkPolicy {
+    /// Envia o conteúdo para onde o link aponta.
+    #[default]
+    Follow,
+    /// Envia o próprio link, recriado do outro lado.
+    Preserve,
+    Skip,
+}
 
 #[tauri::command]
-pub fn list_files(paths: Vec<String>) -> Result<Vec<FileEntry>, String> {
-    let mut entries = Vec::new();
-    for path in paths {
-        let path_buf = PathBuf::from(&path);
-        let metadata = fs::metadata(&path_buf).map_err(|e| e.to_string())?;
-        let name = path_buf
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| path.clone());
-        let checksum = if metadata.is_file() {
-            Some(calculate_checksum(&path_buf).map_err(|e| e.to_string())?)
+pub fn list_files(
+    paths: Vec<String>,
+    symlinks: Option<SymlinkPolicy>,
+) -> Result<Vec<FileEntry>, String> {
+    let roots: Vec<FileEntry> = paths
+        .iter()
+        .map(|path| {
+            let path_buf = PathBuf::from(path);
+            let metadata = fs::metadata(&path_buf).map_err(|e| e.to_string())?;
+            let name = path_buf
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.clone());
+            Ok(FileEntry {
+                path: path.clone(),
+                name,
+                size: metadata.len(),
+                is_dir: metadata.is_dir(),
+                checksum: None,
+                metadata: FileMetadata::default(),
+            })
+        })
+        .collect::<Result<_, String>>()?;
+
+    // diretórios vêm seguidos de todo o conteúdo, com `name` relativo
+    let mut entries =
+        expand_entries(&roots, symlinks.unwrap_or_default()).map_err(|e| e.to_string())?;
+    for entry in &mut entries {
+        if !entry.is_dir && entry.metadata.symlink.is_none() {
+            let checksum =
+                calculate_checksum(&PathBuf::from(&entry.path)).map_err(|e| e.to_string())?;
+            entry.checksum = Some(checksum);
+        }
+    }
+    Ok(entries)
+}
+
+/// Troca cada diretório por ele mesmo seguido de tudo que está abaixo dele,
+/// em ordem, com `name` virando o caminho relativo separado por `/`
+/// (`fotos/2023/praia.jpg`). Diretórios vazios também entram; o `size` de um
+/// diretório é a soma do conteúdo. Entradas repetidas são descartadas e os
+/// metadados de todas são relidos do disco.
+pub(super) fn expand_entries(
+    entries: &[FileEntry],
+    symlinks: SymlinkPolicy,
+) -> anyhow::Result<Vec<FileEntry>> {
+    let mut expanded = Vec::new();
+    let mut seen = HashSet::new();
+    for entry in entries {
+        let path = PathBuf::from(&entry.path);
+        let Some(mut root) = describe(&path, entry.name.clone(), symlinks)? else {
+            continue;
+        };
+        root.checksum = entry.checksum.clone();
+        let mut tree = vec![root];
+        if tree[0].is_dir {
+            let ancestors = vec![fs::canonicalize(&path)?];
+            tree[0].size = walk_dir(&path, &entry.name, symlinks, &ancestors, &mut tree)?;
+        }
+        expanded.extend(tree.into_iter().filter(|e| seen.insert(e.path.clone())));
+    }
+    Ok(expanded)
+}
+
+/// Monta o entry de `path` segundo a política de links; `None` quando ele
+/// fica de fora.
+fn describe(
+    path: &Path,
+    name: String,
+    symlinks: SymlinkPolicy,
+) -> anyhow::Result<Option<FileEntry>> {
+    let link = fs::symlink_metadata(path).with_context(|| format!("ler {}", path.display()))?;
+    let (metadata, target) = match (link.file_type().is_symlink(), symlinks) {
+        (true, SymlinkPolicy::Skip) => return Ok(None),
+        (true, SymlinkPolicy::Preserve) => {
+            let target = fs::read_link(path)?.to_string_lossy().to_string();
+            (link, Some(target))
+        }
+        (true, SymlinkPolicy::Follow) => match fs::metadata(path) {
+            Ok(metadata) => (metadata, None),
+            // link quebrado: não há o que seguir
+            Err(_) => return Ok(None),
+        },
+        (false, _) => (link, None),
+    };
+    let is_dir = target.is_none() && metadata.is_dir();
+    if target.is_none() && !is_dir && !metadata.is_file() {
+        // sockets, fifos e afins
+        return Ok(None);
+    }
+    Ok(Some(FileEntry {
+        path: path.to_string_lossy().to_string(),
+        name,
+        size: if target.is_some() || is_dir {
+            0
+        } else {
+            metadata.len()
+        },
+        is_dir,
+        checksum: None,
+        metadata: FileMetadata {
+            symlink: target,
+            ..FileMetadata::from_fs(&metadata)
+        },
+    }))
+}
+
+fn walk_dir(
+    dir: &Path,
+    relative: &str,
+    symlinks: SymlinkPolicy,
+    ancestors: &[PathBuf],
+    out: &mut Vec<FileEntry>,
+) -> anyhow::Result<u64> {
+    let mut children = fs::read_dir(dir)
+        .with_context(|| format!("listar {}", dir.display()))?
+        .collect::<Result<Vec<_>, _>>()?;
+    children.sort_by_key(|child| child.file_name());
+
+    let mut total = 0u64;
+    for child in children {
+        let path = child.path();
+        let name = format!("{relative}/{}", child.file_name().to_string_lossy());
+        let Some(entry) = describe(&path, name.clone(), symlinks)? else {
+            continue;
+        };
+        if !entry.is_dir {
+            total += entry.size;
+            out.push(entry);
+            continue;
+        }
+
+        // um link seguido pode apontar para um diretório acima: ciclo
+        let canonical = fs::canonicalize(&path)?;
+        if ancestors.contains(&canonical) {
+            tracing::warn!(path = %path.display(), "skipping directory cycle");
+            continue;
+        }
+        let at = out.len();
+        out.push(entry);
+        let ancestors = [ancestors, &[canonical]].concat();
+        let size = walk_dir(&path, &name, symlinks, &ancestors, out)?;
+        out[at].size = size;
+        total += size;
+    }
+    Ok(total)
+}
+
+/// Reaplica mtime e permissões depois que o conteúdo foi verificado. Links não
+/// passam por aqui: mexer neles alteraria o alvo.
+pub(super) fn restore_metadata(path: &Path, metadata: &FileMetadata) -> std::io::Result<()> {
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Ok(());
+    }
+    if let Some(modified) = metadata.modified {
+        // antes das permissões: um modo só leitura impediria a escrita
+        let file = if path.is_dir() {
+            fs::File::open(path)?
         } else {
-            None
+            OpenOptions::new().write(true).open(path)?
         };
-        entries.push(FileEntry {
-            path: path.clone(),
-            name,
-            size: metadata.len(),
-            is_dir: metadata.is_dir(),
-            checksum,
-        });
+        file.set_modified(modified)?;
     }
-    Ok(entries)
+    #[cfg(unix)]
+    if let Some(mode) = metadata.mode {
+        use std::os::unix::fs::PermissionsExt;
+        // setuid, setgid e sticky do remetente não valem aqui
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))?;
+    }
+    Ok(())
+}
+
+/// Alvo de um link preservado em `relative` (relativo ao destino). Não pode
+/// sair do destino: nem absoluto, nem com `..` demais.
+pub(super) fn check_link_target(relative: &Path, target: &str) -> anyhow::Result<()> {
+    let escapes = || anyhow::anyhow!("link {} aponta para fora do destino", relative.display());
+    if target.starts_with(['/', '\\']) || target.contains(':') {
+        return Err(escapes());
+    }
+    let mut depth = relative.components().count() as i64 - 1;
+    for part in target.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => depth -= 1,
+            _ => depth += 1,
+        }
+        if depth < 0 {
+            return Err(escapes());
+        }
+    }
+    Ok(())
+}
+
+/// Pasta de destino de um recebimento. Links deixados por sessões anteriores
+/// continuam lá, então nada é gravado, renomeado ou alterado sem antes
+/// resolver a pasta pai e conferir que ela ainda está dentro da raiz.
+pub(super) struct ReceiveRoot {
+    base: PathBuf,
+    root: PathBuf,
+}
+
+impl ReceiveRoot {
+    pub fn open(destination: &Path) -> anyhow::Result<Self> {
+        fs::create_dir_all(destination)
+            .with_context(|| format!("criar destino {}", destination.display()))?;
+        Ok(Self {
+            base: destination.to_path_buf(),
+            root: fs::canonicalize(destination)?,
+        })
+    }
+
+    /// `relative` já validado por `safe_relative_path`.
+    pub fn join(&self, relative: &Path) -> PathBuf {
+        self.base.join(relative)
+    }
+
+    /// Pai de `path` resolvido dentro da raiz, e `path` em si não é link.
+    pub fn check(&self, path: &Path) -> std::io::Result<()> {
+        let parent = path.parent().unwrap_or(path);
+        if !fs::canonicalize(parent)?.starts_with(&self.root) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("{} sai do destino", path.display()),
+            ));
+        }
+        if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("{} é um link", path.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `create_dir_all` que só atravessa links que continuam na raiz.
+    pub fn create_dirs(&self, relative: &Path) -> std::io::Result<PathBuf> {
+        let mut path = self.base.clone();
+        for part in relative.components() {
+            path.push(part);
+            match fs::symlink_metadata(&path) {
+                Ok(meta) if meta.is_dir() => {}
+                Ok(meta) if meta.file_type().is_symlink() => {
+                    let real = fs::canonicalize(&path)?;
+                    if !real.starts_with(&self.root) || !real.is_dir() {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::PermissionDenied,
+                            format!("{} sai do destino", path.display()),
+                        ));
+                    }
+                }
+                Ok(_) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        format!("{} não é uma pasta", path.display()),
+                    ))
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    self.check(&path)?;
+                    match fs::create_dir(&path) {
+                        Err(err) if err.kind() != std::io::ErrorKind::AlreadyExists => {
+                            return Err(err)
+                        }
+                        _ => {}
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(path)
+    }
+
+    /// Cria o link `relative` → `target` depois de todos os arquivos. Nenhum
+    /// componente do caminho pode ser link (a profundidade checada tem de ser
+    /// a real), nada existente é substituído e um alvo que já existe precisa
+    /// resolver dentro da raiz.
+    pub fn create_link(&self, relative: &Path, target: &str) -> anyhow::Result<()> {
+        let mut parent = self.base.clone();
+        for part in relative.parent().into_iter().flat_map(Path::components) {
+            parent.push(part);
+            anyhow::ensure!(
+                !fs::symlink_metadata(&parent)?.file_type().is_symlink(),
+                "{} passa por um link",
+                relative.display()
+            );
+        }
+        check_link_target(relative, target)?;
+        let link = self.join(relative);
+        let resolved = parent.join(target);
+        if let Ok(real) = fs::canonicalize(&resolved) {
+            anyhow::ensure!(
+                real.starts_with(&self.root),
+                "link {} aponta para fora do destino",
+                relative.display()
+            );
+        }
+        match fs::symlink_metadata(&link) {
+            // retomada: o mesmo link já está lá
+            Ok(meta)
+                if meta.file_type().is_symlink() && fs::read_link(&link)? == Path::new(target) =>
+            {
+                return Ok(());
+            }
+            Ok(_) => anyhow::bail!("{} já existe", link.display()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        create_symlink(&link, target, resolved.is_dir())?;
+        Ok(())
+    }
+}
+
+fn create_symlink(link: &Path, target: &str, is_dir: bool) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let _ = is_dir;
+        std::os::unix::fs::symlink(target, link)
+    }
+    #[cfg(windows)]
+    if is_dir {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+/// Caminho relativo recebido do remetente, já validado para não escapar do
+/// destino: recusa `..`, caminhos absolutos e prefixos de drive (`C:`).
+pub(super) fn safe_relative_path(raw: &str) -> anyhow::Result<PathBuf> {
+    anyhow::ensure!(
+        !raw.starts_with(['/', '\\']),
+        "caminho absoluto recusado: {raw}"
+    );
+    let mut path = PathBuf::new();
+    for part in raw.split(['/', '\\']) {
+        match part {
+            "" | "." => continue,
+            ".." => anyhow::bail!("caminho com '..' recusado: {raw}"),
+            _ if part.contains(':') || part.contains('\0') => {
+                anyhow::bail!("componente inválido em {raw}")
+            }
+            _ => path.push(part),
+        }
+    }
+    anyhow::ensure!(path.components().next().is_some(), "caminho vazio");
+    Ok(path)
 }
 
 #[tauri::command]
@@ -42,13 +380,17 @@ pub fn read_file_range(path: String, start: u64, length: u64) -> Result<Vec<u8>,
 
 #[tauri::command]
 pub fn write_file_range(path: String, start: u64, bytes: Vec<u8>) -> Result<(), String> {
+    write_range(Path::new(&path), start, &bytes).map_err(|e| e.to_string())
+}
+
+pub(super) fn write_range(path: &Path, start: u64, bytes: &[u8]) -> std::io::Result<()> {
     let mut file = OpenOptions::new()
         .create(true)
+        .truncate(false)
         .write(true)
-        .open(&path)
-        .map_err(|e| e.to_string())?;
-    file.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
-    file.write_all(&bytes).map_err(|e| e.to_string())?;
+        .open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    file.write_all(bytes)?;
     Ok(())
 }
 
@@ -66,3 +408,125 @@ fn calculate_checksum(path: &PathBuf) -> anyhow::Result<String> {
     }
     Ok(hasher.finalize().to_hex().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traversal_components_are_rejected() {
+        assert_eq!(
+            safe_relative_path("fotos/2023/praia.jpg").unwrap(),
+            Path::new("fotos").join("2023").join("praia.jpg")
+        );
+        assert_eq!(
+            safe_relative_path("fotos\\./praia.jpg").unwrap(),
+            Path::new("fotos").join("praia.jpg")
+        );
+        for raw in [
+            "../fora.txt",
+            "fotos/../../fora.txt",
+            "..\\fora.txt",
+            "/etc/passwd",
+            "\\\\servidor\\share",
+            "C:\\Windows\\win.ini",
+            "c:relativo.txt",
+            "",
+            "./",
+        ] {
+            assert!(safe_relative_path(raw).is_err(), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn directories_expand_with_relative_names() {
+        let root = tempfile::tempdir().unwrap();
+        let projeto = root.path().join("projeto");
+        fs::create_dir_all(projeto.join("src")).unwrap();
+        fs::create_dir_all(projeto.join("vazio")).unwrap();
+        fs::write(projeto.join("src").join("main.rs"), b"fn main() {}").unwrap();
+        fs::write(projeto.join("LEIAME"), b"oi").unwrap();
+
+        let entries = expand_entries(
+            &[FileEntry {
+                path: projeto.to_string_lossy().to_string(),
+                name: "projeto".into(),
+                size: 0,
+                is_dir: true,
+                checksum: None,
+                metadata: FileMetadata::default(),
+            }],
+            SymlinkPolicy::Follow,
+        )
+        .unwrap();
+        let names: Vec<(&str, bool, u64)> = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.is_dir, e.size))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("projeto", true, 14),
+                ("projeto/LEIAME", false, 2),
+                ("projeto/src", true, 12),
+                ("projeto/src/main.rs", false, 12),
+                ("projeto/vazio", true, 0),
+            ]
+        );
+        // expandir de novo (ex.: saída do list_files) não duplica nada
+        assert_eq!(
+            expand_entries(&entries, SymlinkPolicy::Follow)
+                .unwrap()
+                .len(),
+            entries.len()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_policy_decides_what_is_listed() {
+        let root = tempfile::tempdir().unwrap();
+        let pasta = root.path().join("pasta");
+        fs::create_dir_all(&pasta).unwrap();
+        fs::write(pasta.join("dados.txt"), b"12345").unwrap();
+        std::os::unix::fs::symlink("dados.txt", pasta.join("atalho")).unwrap();
+        // link para o próprio pai: seguir cegamente nunca terminaria
+        std::os::unix::fs::symlink("..", pasta.join("ciclo")).unwrap();
+
+        let root_entry = FileEntry {
+            path: pasta.to_string_lossy().to_string(),
+            name: "pasta".into(),
+            size: 0,
+            is_dir: true,
+            checksum: None,
+            metadata: FileMetadata::default(),
+        };
+        let listed = |policy| {
+            expand_entries(std::slice::from_ref(&root_entry), policy)
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.name, e.size, e.metadata.symlink))
+                .collect::<Vec<_>>()
+        };
+
+        let followed = listed(SymlinkPolicy::Follow);
+        assert!(followed.contains(&("pasta/atalho".into(), 5, None)));
+        assert!(followed
+            .iter()
+            .all(|(name, ..)| !name.starts_with("pasta/ciclo/pasta")));
+        assert_eq!(
+            listed(SymlinkPolicy::Preserve)[1..],
+            [
+                ("pasta/atalho".into(), 0, Some("dados.txt".into())),
+                ("pasta/ciclo".into(), 0, Some("..".into())),
+                ("pasta/dados.txt".into(), 5, None),
+            ]
+        );
+        assert_eq!(listed(SymlinkPolicy::Skip).len(), 2);
+
+        assert!(check_link_target(Path::new("pasta/atalho"), "dados.txt").is_ok());
+        assert!(check_link_target(Path::new("pasta/atalho"), "../vizinho").is_ok());
+        assert!(check_link_target(Path::new("pasta/atalho"), "../../fora").is_err());
+        assert!(check_link_target(Path::new("pasta/atalho"), "/etc/passwd").is_err());
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::manifest::ManifestStore;
+use super::settings::SettingsManager;
+use super::transfer::{manifest_dir, TransferManager, TransferState};
+
+const DEFAULT_PAGE: usize = 50;
+const MAX_PAGE: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+    Send,
+    Receive,
+}
+
+/// Uma sessão encerrada, como aparece na tela de histórico.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferRecord {
+    pub session_id: String,
+    pub direction: Direction,
+    pub peer: String,
+    pub route: String,
+    pub files: Vec<String>,
+    pub bytes: u64,
+    pub duration_ms: u64,
+    /// Milissegundos desde a época Unix.
+    pub finished_at: u64,
+    pub outcome: TransferState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Outcome {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl Outcome {
+    fn matches(self, state: &TransferState) -> bool {
+        matches!(
+            (self, state),
+            (Self::Completed, TransferState::Completed)
+                | (Self::Failed, TransferState::Failed { .. })
+                | (Self::Cancelled, TransferState::Cancelled)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TransferFilter {
+    pub direction: Option<Direction>,
+    pub outcome: Option<Outcome>,
+    /// Trecho do identificador do par, sem diferenciar maiúsculas.
+    pub peer: Option<String>,
+    /// Trecho do id da sessão ou de algum arquivo.
+    pub search: Option<String>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl TransferFilter {
+    fn accepts(&self, record: &TransferRecord) -> bool {
+        if self.direction.is_some_and(|d| d != record.direction) {
+            return false;
+        }
+        if self.outcome.is_some_and(|o| !o.matches(&record.outcome)) {
+            return false;
+        }
+        if self.since.is_some_and(|t| record.finished_at < t)
+            || self.until.is_some_and(|t| record.finished_at > t)
+        {
+            return false;
+        }
+        if let Some(peer) = &self.peer {
+            if !contains_ignore_case(&record.peer, peer) {
+                return false;
+            }
+        }
+        if let Some(search) = &self.search {
+            let hit = contains_ignore_case(&record.session_id, search)
+                || record.files.iter().any(|f| contains_ignore_case(f, search));
+            if !hit {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferPage {
+    pub items: Vec<TransferRecord>,
+    pub total: usize,
+}
+
+#[derive(Clone)]
+pub struct HistoryManager {
+    path: Arc<PathBuf>,
+    lock: Arc<Mutex<()>>,
+}
+
+impl Default for HistoryManager {
+    fn default() -> Self {
+        Self::new(history_path())
+    }
+}
+
+impl HistoryManager {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path: Arc::new(path),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Grava o desfecho de uma sessão; um novo desfecho da mesma sessão e
+    /// direção (retomada depois de falha) substitui o anterior.
+    pub fn record(&self, record: TransferRecord) -> anyhow::Result<()> {
+        let _guard = self.lock.lock();
+        let mut records = self.load()?;
+        records.retain(|r| r.session_id != record.session_id || r.direction != record.direction);
+        records.push(record);
+        self.save(&records)
+    }
+
+    /// Mais recentes primeiro.
+    pub fn list(&self, filter: &TransferFilter) -> anyhow::Result<TransferPage> {
+        let _guard = self.lock.lock();
+        let mut records: Vec<_> = self
+            .load()?
+            .into_iter()
+            .filter(|r| filter.accepts(r))
+            .collect();
+        records.sort_by(|a, b| b.finished_at.cmp(&a.finished_at));
+        let total = records.len();
+        let limit = filter.limit.unwrap_or(DEFAULT_PAGE).min(MAX_PAGE);
+        let items = records
+            .into_iter()
+            .skip(filter.offset)
+            .take(limit)
+            .collect();
+        Ok(TransferPage { items, total })
+    }
+
+    /// Remove a sessão do histórico; `false` se ela não estava lá.
+    pub fn delete(&self, session_id: &str) -> anyhow::Result<bool> {
+        let _guard = self.lock.lock();
+        let mut records = self.load()?;
+        let before = records.len();
+        records.retain(|r| r.session_id != session_id);
+        if records.len() == before {
+            return Ok(false);
+        }
+        self.save(&records)?;
+        Ok(true)
+    }
+
+    /// Esquece registros mais antigos que `retention` e apaga manifestos parados
+    /// há mais tempo que isso. Só saem manifestos de sessões concluídas ou que
+    /// já têm desfecho no histórico: um envio pausado continua retomável.
+    /// Devolve quantos registros saíram.
+    pub fn prune(&self, retention: Duration, manifests: &Path) -> anyhow::Result<usize> {
+        let cutoff = SystemTime::now()
+            .checked_sub(retention)
+            .unwrap_or(UNIX_EPOCH);
+        let cutoff_ms = unix_millis(cutoff);
+
+        let (removed, finished) = {
+            let _guard = self.lock.lock();
+            let mut records = self.load()?;
+            let finished: HashSet<String> = records.iter().map(|r| r.session_id.clone()).collect();
+            let before = records.len();
+            records.retain(|r| r.finished_at >= cutoff_ms);
+            let removed = before - records.len();
+            if removed > 0 {
+                self.save(&records)?;
+            }
+            (removed, finished)
+        };
+
+        for manifest in ManifestStore::scan(manifests) {
+            if !manifest.completed && !finished.contains(&manifest.session_id) {
+                continue;
+            }
+            let mut store = ManifestStore::new(manifests, &manifest.session_id);
+            if store.modified().is_some_and(|modified| modified < cutoff) {
+                store.remove();
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Sem arquivo é histórico vazio; um arquivo ilegível é erro, para o
+    /// próximo `record` não gravar por cima dele.
+    fn load(&self) -> anyhow::Result<Vec<TransferRecord>> {
+        let data = match fs::read(&*self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("ler {}", self.path.display()));
+            }
+        };
+        serde_json::from_slice(&data)
+            .with_context(|| format!("histórico corrompido em {}", self.path.display()))
+    }
+
+    fn save(&self, records: &[TransferRecord]) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp = self.path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_vec(records)?)?;
+        fs::rename(&temp, &*self.path)?;
+        Ok(())
+    }
+}
+
+pub(super) fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn history_path() -> PathBuf {
+    if let Ok(custom) = std::env::var("FLUXSHARE_DATA_DIR") {
+        return PathBuf::from(custom).join("history.json");
+    }
+    let base = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    base.join(".fluxshare").join("history.json")
+}
+
+/// Aplica a retenção configurada; zero dias guarda tudo.
+pub fn apply_retention(history: &HistoryManager, settings: &SettingsManager) {
+    let Ok(settings) = settings.get_settings() else {
+        return;
+    };
+    if settings.history_retention_days == 0 {
+        return;
+    }
+    let retention = Duration::from_secs(u64::from(settings.history_retention_days) * 86_400);
+    match history.prune(retention, &manifest_dir()) {
+        Ok(removed) if removed > 0 => tracing::info!(removed, "history_pruned"),
+        Ok(_) => {}
+        Err(err) => tracing::warn!(?err, "history prune failed"),
+    }
+}
+
+#[tauri::command]
+pub fn list_transfers(
+    history: tauri::State<'_, HistoryManager>,
+    filter: Option<TransferFilter>,
+) -> Result<TransferPage, String> {
+    history
+        .list(&filter.unwrap_or_default())
+        .map_err(|e| format!("{e:#}"))
+}
+
+#[tauri::command]
+pub fn delete_transfer(
+    history: tauri::State<'_, HistoryManager>,
+    transfer_manager: tauri::State<'_, TransferManager>,
+    session_id: String,
+) -> Result<(), String> {
+    if transfer_manager.is_running(&session_id) {
+        return Err("transferência ainda em andamento".into());
+    }
+    let removed = history.delete(&session_id).map_err(|e| e.to_string())?;
+    if !removed {
+        return Err(format!("sessão desconhecida: {session_id}"));
+    }
+    ManifestStore::new(&manifest_dir(), &session_id).remove();
+    transfer_manager.forget(&session_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::transfer::TransferManifest;
+
+    fn record(session_id: &str, direction: Direction, finished_at: u64) -> TransferRecord {
+        TransferRecord {
+            session_id: session_id.into(),
+            direction,
+            peer: "quic:10.0.0.2:5000".into(),
+            route: "quic".into(),
+            files: vec![format!("{session_id}/relatorio.pdf")],
+            bytes: 1024,
+            duration_ms: 10,
+            finished_at,
+            outcome: TransferState::Completed,
+        }
+    }
+
+    #[test]
+    fn history_filters_pages_and_deletes() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = HistoryManager::new(dir.path().join("history.json"));
+        for i in 0..5 {
+            history
+                .record(record(&format!("s{i}"), Direction::Send, i))
+                .unwrap();
+        }
+        history
+            .record(record("s1", Direction::Receive, 10))
+            .unwrap();
+        let mut failed = record("s2", Direction::Send, 20);
+        failed.outcome = TransferState::Failed {
+            reason: "conexão perdida".into(),
+        };
+        // nova tentativa da mesma sessão substitui a anterior
+        history.record(failed).unwrap();
+
+        let all = history.list(&TransferFilter::default()).unwrap();
+        assert_eq!(all.total, 6);
+        assert_eq!(all.items[0].session_id, "s2");
+
+        let page = history
+            .list(&TransferFilter {
+                direction: Some(Direction::Send),
+                offset: 1,
+                limit: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(page.total, 5);
+        let ids: Vec<_> = page.items.iter().map(|r| r.session_id.as_str()).collect();
+        assert_eq!(ids, ["s4", "s3"]);
+
+        let failed = history
+            .list(&TransferFilter {
+                outcome: Some(Outcome::Failed),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(failed.total, 1);
+        let searched = history
+            .list(&TransferFilter {
+                search: Some("S3/RELATORIO".into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(searched.items[0].session_id, "s3");
+
+        assert!(history.delete("s1").unwrap());
+        assert!(!history.delete("s1").unwrap());
+        assert_eq!(history.list(&TransferFilter::default()).unwrap().total, 4);
+
+        // arquivo ilegível vira erro em vez de ser sobrescrito
+        fs::write(dir.path().join("history.json"), b"{corrompido").unwrap();
+        assert!(history.list(&TransferFilter::default()).is_err());
+        assert!(history.record(record("s9", Direction::Send, 30)).is_err());
+        assert_eq!(
+            fs::read(dir.path().join("history.json")).unwrap(),
+            b"{corrompido"
+        );
+    }
+
+    #[test]
+    fn retention_drops_old_records_and_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifests = dir.path().join("manifests");
+        fs::create_dir_all(&manifests).unwrap();
+        let history = HistoryManager::new(dir.path().join("history.json"));
+
+        let now = unix_millis(SystemTime::now());
+        let old = now - 10 * 86_400_000;
+        history
+            .record(record("antiga", Direction::Send, old))
+            .unwrap();
+        history
+            .record(record("nova", Direction::Send, now))
+            .unwrap();
+
+        // "pronta" terminou sem registro; "pausada" ainda pode ser retomada
+        for (session_id, completed) in [
+            ("antiga", false),
+            ("nova", false),
+            ("pronta", true),
+            ("pausada", false),
+        ] {
+            let mut store = ManifestStore::new(&manifests, session_id);
+            store
+                .checkpoint(&TransferManifest {
+                    session_id: session_id.into(),
+                    completed,
+                    ..Default::default()
+                })
+                .unwrap();
+            fs::write(manifests.join(format!("{session_id}.journal")), b"").unwrap();
+        }
+        let week_ago = SystemTime::now() - Duration::from_secs(10 * 86_400);
+        for name in [
+            "antiga.json",
+            "antiga.journal",
+            "pronta.json",
+            "pronta.journal",
+            "pausada.json",
+            "pausada.journal",
+        ] {
+            let file = fs::File::options()
+                .write(true)
+                .open(manifests.join(name))
+                .unwrap();
+            file.set_modified(week_ago).unwrap();
+        }
+
+        let removed = history
+            .prune(Duration::from_secs(7 * 86_400), &manifests)
+            .unwrap();
+        assert_eq!(removed, 1);
+        let left = history.list(&TransferFilter::default()).unwrap();
+        assert_eq!(left.items.len(), 1);
+        assert_eq!(left.items[0].session_id, "nova");
+        assert!(!manifests.join("antiga.json").exists());
+        assert!(!manifests.join("antiga.journal").exists());
+        assert!(!manifests.join("pronta.json").exists());
+        assert!(manifests.join("pausada.json").exists());
+        assert!(manifests.join("pausada.journal").exists());
+        assert!(manifests.join("nova.json").exists());
+    }
+}
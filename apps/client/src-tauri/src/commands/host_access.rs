@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use rand::RngCore;
+
+pub(super) const COOKIE_NAME: &str = "fluxshare_host";
+const COOKIE_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+// erros de senha sem espera; a partir daí cada erro dobra a espera
+const FREE_ATTEMPTS: u32 = 3;
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(5 * 60);
+
+/// Senha de uma sessão de host. O cookie de acesso é `<expira>.<mac>`, com o
+/// mac em blake3 chaveado por um segredo sorteado a cada `start_host`: trocar
+/// de sessão invalida todos os cookies antigos.
+#[derive(Clone)]
+pub(super) struct HostAccess {
+    password: blake3::Hash,
+    secret: [u8; 32],
+    throttle: Arc<LoginThrottle>,
+}
+
+impl HostAccess {
+    pub(super) fn new(password: &str) -> Self {
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        Self {
+            password: blake3::hash(password.as_bytes()),
+            secret,
+            throttle: Arc::default(),
+        }
+    }
+
+    pub(super) fn check_password(&self, candidate: &str) -> bool {
+        // comparação de `Hash` é em tempo constante
+        blake3::hash(candidate.as_bytes()) == self.password
+    }
+
+    /// Confere a senha respeitando a espera do cliente depois de erros
+    /// seguidos. `Err` traz quanto falta para a próxima tentativa.
+    pub(super) fn login(
+        &self,
+        client: &str,
+        candidate: &str,
+        now: Instant,
+    ) -> Result<bool, Duration> {
+        self.throttle.check(client, now)?;
+        let accepted = self.check_password(candidate);
+        if accepted {
+            self.throttle.forget(client);
+        } else {
+            self.throttle.failed(client, now);
+        }
+        Ok(accepted)
+    }
+
+    pub(super) fn issue_cookie(&self, now: SystemTime) -> String {
+        let expires = unix_secs(now + COOKIE_TTL);
+        format!("{expires}.{}", self.mac(expires).to_hex())
+    }
+
+    pub(super) fn verify_cookie(&self, value: &str, now: SystemTime) -> bool {
+        let Some((expires, mac)) = value.split_once('.') else {
+            return false;
+        };
+        let Ok(expires) = expires.parse::<u64>() else {
+            return false;
+        };
+        let Ok(mac) = blake3::Hash::from_hex(mac) else {
+            return false;
+        };
+        mac == self.mac(expires) && unix_secs(now) < expires
+    }
+
+    /// Cabeçalho `Set-Cookie` para o navegador que acertou a senha; `secure`
+    /// quando o pedido chegou por https (pelo túnel).
+    pub(super) fn set_cookie_header(&self, now: SystemTime, secure: bool) -> String {
+        format!(
+            "{COOKIE_NAME}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{}",
+            self.issue_cookie(now),
+            COOKIE_TTL.as_secs(),
+            if secure { "; Secure" } else { "" }
+        )
+    }
+
+    fn mac(&self, expires: u64) -> blake3::Hash {
+        blake3::keyed_hash(&self.secret, format!("{COOKIE_NAME}:{expires}").as_bytes())
+    }
+}
+
+/// Erros de senha por cliente (o IP que o túnel repassa).
+#[derive(Default)]
+struct LoginThrottle {
+    clients: Mutex<HashMap<String, Failures>>,
+}
+
+struct Failures {
+    count: u32,
+    retry_at: Instant,
+}
+
+impl LoginThrottle {
+    fn check(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        match self.clients.lock().get(client) {
+            Some(failures) if failures.retry_at > now => Err(failures.retry_at - now),
+            _ => Ok(()),
+        }
+    }
+
+    fn failed(&self, client: &str, now: Instant) {
+        let mut clients = self.clients.lock();
+        // quem já pode tentar de novo há muito tempo é esquecido
+        clients.retain(|_, failures| failures.retry_at + BACKOFF_MAX > now);
+        let failures = clients.entry(client.to_string()).or_insert(Failures {
+            count: 0,
+            retry_at: now,
+        });
+        failures.count += 1;
+        if let Some(extra) = failures.count.checked_sub(FREE_ATTEMPTS + 1) {
+            let wait = BACKOFF_BASE.saturating_mul(1 << extra.min(16));
+            failures.retry_at = now + wait.min(BACKOFF_MAX);
+        }
+    }
+
+    fn forget(&self, client: &str) {
+        self.clients.lock().remove(client);
+    }
+}
+
+/// Valor do nosso cookie dentro de um cabeçalho `Cookie`.
+pub(super) fn cookie_value(header: &str) -> Option<&str> {
+    header
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == COOKIE_NAME)
+        .map(|(_, value)| value)
+}
+
+/// 128 bits aleatórios em hex, para ids de arquivo e links de sessão.
+pub(super) fn random_token() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookie_is_bound_to_session_and_expiry() {
+        let now = SystemTime::now();
+        let access = HostAccess::new("s3nha");
+        assert!(access.check_password("s3nha"));
+        assert!(!access.check_password("senha"));
+
+        let set_cookie = access.set_cookie_header(now, false);
+        assert!(!set_cookie.contains("Secure"));
+        assert!(access.set_cookie_header(now, true).ends_with("; Secure"));
+        let (pair, _) = set_cookie.split_once(';').unwrap();
+        let header = format!("tema=escuro; {pair}");
+        let value = cookie_value(&header).unwrap();
+        assert!(access.verify_cookie(value, now));
+        assert!(!access.verify_cookie(value, now + COOKIE_TTL + Duration::from_secs(1)));
+
+        // prorrogar a validade na mão quebra o mac
+        let (expires, mac) = value.split_once('.').unwrap();
+        let forged = format!("{}.{mac}", expires.parse::<u64>().unwrap() + 3600);
+        assert!(!access.verify_cookie(&forged, now));
+
+        // outra sessão de host, mesma senha: o cookie antigo não vale
+        let next = HostAccess::new("s3nha");
+        assert!(!next.verify_cookie(value, now));
+        assert!(!access.verify_cookie("lixo", now));
+    }
+
+    #[test]
+    fn repeated_wrong_passwords_back_off_per_client() {
+        let access = HostAccess::new("s3nha");
+        let now = Instant::now();
+        for _ in 0..FREE_ATTEMPTS {
+            assert_eq!(access.login("1.2.3.4", "errada", now), Ok(false));
+        }
+        // o erro seguinte ainda é avaliado, mas passa a impor espera
+        assert_eq!(access.login("1.2.3.4", "errada", now), Ok(false));
+        assert_eq!(access.login("1.2.3.4", "s3nha", now), Err(BACKOFF_BASE));
+        // outro cliente não herda a espera
+        assert_eq!(access.login("5.6.7.8", "s3nha", now), Ok(true));
+
+        let later = now + BACKOFF_BASE;
+        assert_eq!(access.login("1.2.3.4", "errada", later), Ok(false));
+        assert_eq!(
+            access.login("1.2.3.4", "s3nha", later),
+            Err(BACKOFF_BASE * 2)
+        );
+        let later = later + BACKOFF_BASE * 2;
+        assert_eq!(access.login("1.2.3.4", "s3nha", later), Ok(true));
+        assert_eq!(access.login("1.2.3.4", "errada", later), Ok(false));
+    }
+}
@@ -0,0 +1,166 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::files::safe_relative_path;
+
+/// Item de uma pasta hospedada, já confinado à raiz.
+#[derive(Debug, Clone)]
+pub(super) struct DirItem {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub is_link: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// Caminho pedido na URL dentro de `root` (já canônica). Links simbólicos
+/// são resolvidos antes da checagem, então nenhum alvo fora da raiz passa.
+pub(super) fn resolve(root: &Path, relative: &str) -> io::Result<PathBuf> {
+    let joined = if relative.trim_matches(['/', '\\']).is_empty() {
+        root.to_path_buf()
+    } else {
+        let relative = safe_relative_path(relative)
+            .map_err(|error| io::Error::new(io::ErrorKind::PermissionDenied, error.to_string()))?;
+        root.join(relative)
+    };
+    let canonical = fs::canonicalize(joined)?;
+    if !canonical.starts_with(root) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "caminho fora da pasta hospedada",
+        ));
+    }
+    Ok(canonical)
+}
+
+/// Conteúdo direto de `dir`: pastas primeiro, depois arquivos, por nome.
+/// Links que saem da raiz, quebrados ou para sockets e afins ficam de fora.
+pub(super) fn list(root: &Path, dir: &Path) -> io::Result<Vec<DirItem>> {
+    let mut items = Vec::new();
+    for child in fs::read_dir(dir)? {
+        let child = child?;
+        let name = child.file_name().to_string_lossy().to_string();
+        let is_link = child.file_type()?.is_symlink();
+        let Ok(path) = fs::canonicalize(child.path()) else {
+            continue;
+        };
+        if !path.starts_with(root) {
+            continue;
+        }
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        if !metadata.is_dir() && !metadata.is_file() {
+            continue;
+        }
+        items.push(DirItem {
+            name,
+            is_dir: metadata.is_dir(),
+            is_link,
+            size: if metadata.is_file() {
+                metadata.len()
+            } else {
+                0
+            },
+            modified: metadata.modified().ok(),
+            path,
+        });
+    }
+    items.sort_by(|a, b| match (a.is_dir, b.is_dir) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        _ => a.name.cmp(&b.name),
+    });
+    Ok(items)
+}
+
+/// Todos os arquivos abaixo de `dir`, com `name` relativo a ele (separado por
+/// `/`). Cada pasta canônica entra uma vez só, então links em ciclo param;
+/// pastas de verdade vêm antes dos links, para o nome real prevalecer.
+pub(super) fn walk_files(root: &Path, dir: &Path) -> io::Result<Vec<DirItem>> {
+    let mut out = Vec::new();
+    let mut visited = HashSet::new();
+    walk(root, dir, "", &mut visited, &mut out)?;
+    Ok(out)
+}
+
+fn walk(
+    root: &Path,
+    dir: &Path,
+    prefix: &str,
+    visited: &mut HashSet<PathBuf>,
+    out: &mut Vec<DirItem>,
+) -> io::Result<()> {
+    if !visited.insert(dir.to_path_buf()) {
+        tracing::warn!(path = %dir.display(), "hosted_dir_cycle_skipped");
+        return Ok(());
+    }
+    let (real, links): (Vec<_>, Vec<_>) =
+        list(root, dir)?.into_iter().partition(|item| !item.is_link);
+    for item in real.into_iter().chain(links) {
+        let name = format!("{prefix}{}", item.name);
+        if item.is_dir {
+            walk(root, &item.path, &format!("{name}/"), visited, out)?;
+        } else {
+            out.push(DirItem { name, ..item });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_stay_inside_the_hosted_root() {
+        let base = tempfile::tempdir().unwrap();
+        let root = base.path().join("raiz");
+        fs::create_dir_all(root.join("fotos/2024")).unwrap();
+        fs::write(root.join("fotos/2024/praia.jpg"), b"jpg").unwrap();
+        fs::write(root.join("leiame.txt"), b"oi").unwrap();
+        fs::write(base.path().join("segredo.txt"), b"nao").unwrap();
+        let root = fs::canonicalize(&root).unwrap();
+
+        assert_eq!(resolve(&root, "").unwrap(), root);
+        assert_eq!(
+            resolve(&root, "fotos/2024/praia.jpg").unwrap(),
+            root.join("fotos/2024/praia.jpg")
+        );
+        assert!(resolve(&root, "../segredo.txt").is_err());
+        assert!(resolve(&root, "fotos/../../segredo.txt").is_err());
+        assert!(resolve(&root, "/etc/passwd").is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(base.path().join("segredo.txt"), root.join("fuga")).unwrap();
+            std::os::unix::fs::symlink(root.join("fotos"), root.join("atalho")).unwrap();
+            std::os::unix::fs::symlink(&root, root.join("fotos/volta")).unwrap();
+            assert!(resolve(&root, "fuga").is_err());
+            assert!(resolve(&root, "atalho/2024/praia.jpg").is_ok());
+        }
+
+        let names: Vec<_> = list(&root, &root)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.name)
+            .collect();
+        #[cfg(unix)]
+        assert_eq!(names, ["atalho", "fotos", "leiame.txt"]);
+        #[cfg(not(unix))]
+        assert_eq!(names, ["fotos", "leiame.txt"]);
+
+        // o atalho e a volta apontam para pastas já visitadas: nada repetido
+        let files: Vec<_> = walk_files(&root, &root)
+            .unwrap()
+            .into_iter()
+            .map(|item| item.name)
+            .collect();
+        assert_eq!(files, ["fotos/2024/praia.jpg", "leiame.txt"]);
+    }
+}
@@ -0,0 +1,378 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::compression::ChunkEncoding;
+use super::transfer::{ChunkInfo, FileManifest, TransferManifest};
+
+// registros no journal antes de compactar num snapshot novo
+const CHECKPOINT_EVERY: usize = 512;
+
+/// Uma mutação do manifest, uma por linha no journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum JournalRecord {
+    /// Cria o entry do arquivo ou atualiza o salt, mantendo os chunks já confirmados.
+    #[serde(rename_all = "camelCase")]
+    File {
+        path: String,
+        size: u64,
+        key_salt: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Chunk {
+        path: String,
+        index: u64,
+        hash: String,
+        size: u64,
+        #[serde(default)]
+        encoding: ChunkEncoding,
+        #[serde(default)]
+        compressed_size: Option<u64>,
+    },
+    /// Raiz blake3 do conteúdo atual do arquivo.
+    #[serde(rename_all = "camelCase", alias = "finished")]
+    Root {
+        path: String,
+        #[serde(alias = "finalHash")]
+        root: String,
+    },
+    /// Tamanho, mtime e inode da origem ao entrar na sessão. Se diferem do que
+    /// foi gravado antes, os chunks confirmados eram de outro conteúdo.
+    #[serde(rename_all = "camelCase")]
+    Source {
+        path: String,
+        size: u64,
+        #[serde(default)]
+        modified: Option<SystemTime>,
+        #[serde(default)]
+        inode: Option<u64>,
+    },
+    /// Descarta os chunks confirmados do arquivo (rejeitado ou modificado).
+    #[serde(rename_all = "camelCase")]
+    Reset { path: String },
+}
+
+impl JournalRecord {
+    fn apply_to(self, manifest: &mut TransferManifest) {
+        match self {
+            Self::File {
+                path,
+                size,
+                key_salt,
+            } => {
+                let entry = manifest
+                    .files
+                    .entry(path.clone())
+                    .or_insert_with(|| FileManifest {
+                        path,
+                        size,
+                        ..Default::default()
+                    });
+                entry.key_salt = key_salt;
+            }
+            Self::Chunk {
+                path,
+                index,
+                hash,
+                size,
+                encoding,
+                compressed_size,
+            } => {
+                if let Some(entry) = manifest.files.get_mut(&path) {
+                    entry.chunks.retain(|c| c.index != index);
+                    entry.chunks.push(ChunkInfo {
+                        index,
+                        hash,
+                        size,
+                        encoding,
+                        compressed_size,
+                    });
+                }
+            }
+            Self::Root { path, root } => {
+                if let Some(entry) = manifest.files.get_mut(&path) {
+                    entry.root = Some(root);
+                }
+            }
+            Self::Source {
+                path,
+                size,
+                modified,
+                inode,
+            } => {
+                if let Some(entry) = manifest.files.get_mut(&path) {
+                    if (entry.size, entry.modified, entry.inode) != (size, modified, inode) {
+                        entry.chunks.clear();
+                        entry.root = None;
+                    }
+                    entry.size = size;
+                    entry.modified = modified;
+                    entry.inode = inode;
+                }
+            }
+            Self::Reset { path } => {
+                if let Some(entry) = manifest.files.get_mut(&path) {
+                    entry.chunks.clear();
+                }
+            }
+        }
+    }
+}
+
+/// Manifest de uma sessão em disco: snapshot `<sessão>.json` trocado de forma
+/// atômica (temp + rename) mais um journal `<sessão>.journal` só de acréscimo.
+pub struct ManifestStore {
+    snapshot: PathBuf,
+    journal: PathBuf,
+    writer: Option<File>,
+    pending: usize,
+}
+
+impl ManifestStore {
+    pub fn new(dir: &Path, session_id: &str) -> Self {
+        Self {
+            snapshot: dir.join(format!("{session_id}.json")),
+            journal: dir.join(format!("{session_id}.journal")),
+            writer: None,
+            pending: 0,
+        }
+    }
+
+    /// Snapshot + replay do journal. Um registro final rasgado (queda no meio
+    /// do append) é ignorado e cortado do arquivo.
+    pub fn load(&mut self) -> Option<TransferManifest> {
+        let data = fs::read(&self.snapshot).ok()?;
+        let mut manifest: TransferManifest = serde_json::from_slice(&data).ok()?;
+
+        let journal = fs::read(&self.journal).unwrap_or_default();
+        let mut valid = 0usize;
+        for line in journal.split_inclusive(|b| *b == b'\n') {
+            let Some(record) = line
+                .strip_suffix(b"\n")
+                .and_then(|raw| serde_json::from_slice::<JournalRecord>(raw).ok())
+            else {
+                break;
+            };
+            record.apply_to(&mut manifest);
+            valid += line.len();
+            self.pending += 1;
+        }
+        if valid < journal.len() {
+            tracing::warn!(journal = %self.journal.display(), "descartando registro incompleto");
+            if let Ok(file) = OpenOptions::new().write(true).open(&self.journal) {
+                let _ = file.set_len(valid as u64);
+            }
+        }
+        Some(manifest)
+    }
+
+    pub fn apply(
+        &mut self,
+        manifest: &mut TransferManifest,
+        record: JournalRecord,
+    ) -> anyhow::Result<()> {
+        record.clone().apply_to(manifest);
+        if self.pending >= CHECKPOINT_EVERY {
+            return self.checkpoint(manifest);
+        }
+
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => self.writer.insert(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.journal)
+                    .with_context(|| format!("abrir {}", self.journal.display()))?,
+            ),
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        // um único write: ou a linha entra inteira ou fica rasgada no fim
+        writer.write_all(&line)?;
+        self.pending += 1;
+        Ok(())
+    }
+
+    pub fn checkpoint(&mut self, manifest: &TransferManifest) -> anyhow::Result<()> {
+        if let Some(dir) = self.snapshot.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temp = self.snapshot.with_extension("json.tmp");
+        let mut file = File::create(&temp)?;
+        file.write_all(&serde_json::to_vec(manifest)?)?;
+        file.sync_all()?;
+        fs::rename(&temp, &self.snapshot)
+            .with_context(|| format!("substituir {}", self.snapshot.display()))?;
+
+        // o snapshot já cobre o journal; se cair antes do corte, o replay é idempotente
+        self.writer = None;
+        File::create(&self.journal)?;
+        self.pending = 0;
+        Ok(())
+    }
+
+    /// Manifests de todas as sessões guardadas em `dir`, em ordem de id.
+    pub fn scan(dir: &Path) -> Vec<TransferManifest> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut manifests: Vec<TransferManifest> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                // outros JSON do diretório (índice de chunks) não viram manifest
+                let session_id = path.file_stem()?.to_str()?;
+                Self::new(dir, session_id).load()
+            })
+            .collect();
+        manifests.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        manifests
+    }
+
+    /// Última gravação no snapshot ou no journal.
+    pub fn modified(&self) -> Option<SystemTime> {
+        [&self.snapshot, &self.journal]
+            .into_iter()
+            .filter_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+            .max()
+    }
+
+    pub fn remove(&mut self) {
+        self.writer = None;
+        let _ = fs::remove_file(&self.snapshot);
+        let _ = fs::remove_file(&self.journal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn chunk(index: u64) -> JournalRecord {
+        JournalRecord::Chunk {
+            path: "/tmp/a".into(),
+            index,
+            hash: format!("{index:02x}"),
+            size: 10,
+            encoding: ChunkEncoding::Raw,
+            compressed_size: None,
+        }
+    }
+
+    #[test]
+    fn torn_final_record_is_dropped_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = ManifestStore::new(dir.path(), "rasgado");
+        let mut manifest = TransferManifest {
+            session_id: "rasgado".into(),
+            files: HashMap::new(),
+            ..Default::default()
+        };
+        store.checkpoint(&manifest).unwrap();
+        store
+            .apply(
+                &mut manifest,
+                JournalRecord::File {
+                    path: "/tmp/a".into(),
+                    size: 40,
+                    key_salt: None,
+                },
+            )
+            .unwrap();
+        for index in 0..3 {
+            store.apply(&mut manifest, chunk(index)).unwrap();
+        }
+        drop(store);
+
+        // simula queda no meio do quarto append
+        let journal = dir.path().join("rasgado.journal");
+        let mut file = OpenOptions::new().append(true).open(&journal).unwrap();
+        file.write_all(br#"{"op":"chunk","path":"/tmp/a","ind"#)
+            .unwrap();
+        drop(file);
+
+        let mut store = ManifestStore::new(dir.path(), "rasgado");
+        let mut loaded = store.load().expect("manifest recuperado");
+        assert_eq!(loaded.files["/tmp/a"].chunks.len(), 3);
+
+        // o resto rasgado foi cortado: novos registros continuam legíveis
+        store.apply(&mut loaded, chunk(3)).unwrap();
+        drop(store);
+        let reloaded = ManifestStore::new(dir.path(), "rasgado").load().unwrap();
+        assert_eq!(reloaded.files["/tmp/a"].chunks.len(), 4);
+    }
+
+    #[test]
+    fn journal_is_compacted_into_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = ManifestStore::new(dir.path(), "compacto");
+        let mut manifest = TransferManifest::default();
+        store.checkpoint(&manifest).unwrap();
+        store
+            .apply(
+                &mut manifest,
+                JournalRecord::File {
+                    path: "/tmp/a".into(),
+                    size: 10 * (CHECKPOINT_EVERY as u64 + 10),
+                    key_salt: None,
+                },
+            )
+            .unwrap();
+        for index in 0..CHECKPOINT_EVERY as u64 + 10 {
+            store.apply(&mut manifest, chunk(index)).unwrap();
+        }
+
+        let journal = fs::read_to_string(dir.path().join("compacto.journal")).unwrap();
+        assert!(journal.lines().count() < CHECKPOINT_EVERY);
+        let loaded = ManifestStore::new(dir.path(), "compacto").load().unwrap();
+        assert_eq!(loaded.files["/tmp/a"].chunks.len(), CHECKPOINT_EVERY + 10);
+    }
+
+    #[test]
+    fn changed_source_drops_confirmed_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = ManifestStore::new(dir.path(), "origem");
+        let mut manifest = TransferManifest::default();
+        store.checkpoint(&manifest).unwrap();
+        let source = |modified: u64| JournalRecord::Source {
+            path: "/tmp/a".into(),
+            size: 40,
+            modified: Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(modified)),
+            inode: Some(7),
+        };
+        store
+            .apply(
+                &mut manifest,
+                JournalRecord::File {
+                    path: "/tmp/a".into(),
+                    size: 40,
+                    key_salt: None,
+                },
+            )
+            .unwrap();
+        store.apply(&mut manifest, source(100)).unwrap();
+        for index in 0..2 {
+            store.apply(&mut manifest, chunk(index)).unwrap();
+        }
+
+        // mesma origem numa nova tentativa: nada se perde
+        store.apply(&mut manifest, source(100)).unwrap();
+        assert_eq!(manifest.files["/tmp/a"].chunks.len(), 2);
+
+        store.apply(&mut manifest, source(200)).unwrap();
+        drop(store);
+        let loaded = ManifestStore::new(dir.path(), "origem").load().unwrap();
+        assert!(loaded.files["/tmp/a"].chunks.is_empty());
+        assert!(loaded.files["/tmp/a"].modified.is_some());
+    }
+}
@@ -0,0 +1,244 @@
+use std::io::{self, Read};
+
+use blake3::hazmat::{
+    left_subtree_len, merge_subtrees_non_root, merge_subtrees_root, ChainingValue, HasherExt, Mode,
+};
+
+/// Granularidade das folhas: 64 chunks blake3. Faixas verificáveis começam e
+/// terminam em múltiplos disto (ou no fim do arquivo).
+pub const GROUP_LEN: u64 = 64 * 1024;
+const CV_LEN: usize = 32;
+
+/// Árvore blake3 de um arquivo guardada fora do conteúdo: os chaining values
+/// das folhas de `GROUP_LEN` bytes. A raiz é o próprio `blake3::hash` do arquivo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outboard {
+    size: u64,
+    root: blake3::Hash,
+    // vazio quando o arquivo cabe numa folha só (a raiz já cobre tudo)
+    leaves: Vec<ChainingValue>,
+}
+
+impl Outboard {
+    pub fn from_reader(mut reader: impl Read, size: u64) -> io::Result<Self> {
+        let mut builder = OutboardBuilder::default();
+        let mut buffer = vec![0u8; GROUP_LEN as usize];
+        let mut remaining = size;
+        while remaining > 0 {
+            let len = GROUP_LEN.min(remaining) as usize;
+            reader.read_exact(&mut buffer[..len])?;
+            builder.update(&buffer[..len]);
+            remaining -= len as u64;
+        }
+        Ok(builder.finish())
+    }
+
+    /// Reconstrói o outboard recebido e confere as folhas contra a raiz.
+    pub fn from_parts(size: u64, root: &str, bytes: &[u8]) -> anyhow::Result<Self> {
+        let root = blake3::Hash::from_hex(root).map_err(|_| anyhow::anyhow!("raiz inválida"))?;
+        let expected = if size <= GROUP_LEN {
+            0
+        } else {
+            group_count(size)
+        };
+        anyhow::ensure!(
+            bytes.len() == expected * CV_LEN,
+            "outboard com {} bytes, esperado {}",
+            bytes.len(),
+            expected * CV_LEN
+        );
+        let leaves: Vec<ChainingValue> = bytes
+            .chunks_exact(CV_LEN)
+            .map(|cv| cv.try_into().expect("chunks_exact"))
+            .collect();
+        if !leaves.is_empty() {
+            anyhow::ensure!(
+                root_from_leaves(size, &leaves) == root,
+                "outboard não confere com a raiz"
+            );
+        }
+        Ok(Self { size, root, leaves })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn root(&self) -> String {
+        self.root.to_hex().to_string()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.leaves.concat()
+    }
+
+    /// Confere `data` lido a partir de `offset`. Só as folhas inteiramente
+    /// contidas na faixa são checadas; devolve quantos bytes foram cobertos.
+    pub fn verify_range(&self, offset: u64, data: &[u8]) -> anyhow::Result<u64> {
+        let end = offset + data.len() as u64;
+        anyhow::ensure!(end <= self.size, "faixa além do fim do arquivo");
+        if self.leaves.is_empty() {
+            if offset != 0 || end != self.size {
+                return Ok(0);
+            }
+            anyhow::ensure!(blake3::hash(data) == self.root, "conteúdo não confere");
+            return Ok(end);
+        }
+
+        let mut group = offset.div_ceil(GROUP_LEN);
+        let mut covered = 0u64;
+        loop {
+            let group_start = group * GROUP_LEN;
+            let group_end = (group_start + GROUP_LEN).min(self.size);
+            if group_start >= self.size || group_end > end {
+                break;
+            }
+            let slice = &data[(group_start - offset) as usize..(group_end - offset) as usize];
+            anyhow::ensure!(
+                leaf_cv(group_start, slice) == self.leaves[group as usize],
+                "bytes {group_start}-{group_end} não conferem com o outboard"
+            );
+            covered += group_end - group_start;
+            group += 1;
+        }
+        Ok(covered)
+    }
+}
+
+/// Monta o outboard a partir de fatias consecutivas de qualquer tamanho.
+#[derive(Default)]
+pub struct OutboardBuilder {
+    size: u64,
+    // a folha só é fechada quando chega mais dado: um arquivo de exatamente
+    // `GROUP_LEN` bytes ainda precisa virar raiz, não folha
+    pending: Vec<u8>,
+    leaves: Vec<ChainingValue>,
+}
+
+impl OutboardBuilder {
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.pending.len() as u64 == GROUP_LEN {
+                self.flush();
+            }
+            let take = (GROUP_LEN as usize - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            self.size += take as u64;
+            data = &data[take..];
+        }
+    }
+
+    pub fn finish(mut self) -> Outboard {
+        if self.leaves.is_empty() {
+            return Outboard {
+                size: self.size,
+                root: blake3::hash(&self.pending),
+                leaves: Vec::new(),
+            };
+        }
+        self.flush();
+        Outboard {
+            size: self.size,
+            root: root_from_leaves(self.size, &self.leaves),
+            leaves: self.leaves,
+        }
+    }
+
+    fn flush(&mut self) {
+        let offset = self.leaves.len() as u64 * GROUP_LEN;
+        self.leaves.push(leaf_cv(offset, &self.pending));
+        self.pending.clear();
+    }
+}
+
+fn group_count(size: u64) -> usize {
+    size.div_ceil(GROUP_LEN) as usize
+}
+
+fn leaf_cv(offset: u64, data: &[u8]) -> ChainingValue {
+    blake3::Hasher::new()
+        .set_input_offset(offset)
+        .update(data)
+        .finalize_non_root()
+}
+
+// `leaves` cobre `len` bytes, com `len > GROUP_LEN`
+fn root_from_leaves(len: u64, leaves: &[ChainingValue]) -> blake3::Hash {
+    let left_len = left_subtree_len(len);
+    let (left, right) = leaves.split_at((left_len / GROUP_LEN) as usize);
+    merge_subtrees_root(
+        &subtree_cv(left_len, left),
+        &subtree_cv(len - left_len, right),
+        Mode::Hash,
+    )
+}
+
+fn subtree_cv(len: u64, leaves: &[ChainingValue]) -> ChainingValue {
+    if len <= GROUP_LEN {
+        return leaves[0];
+    }
+    let left_len = left_subtree_len(len);
+    let (left, right) = leaves.split_at((left_len / GROUP_LEN) as usize);
+    merge_subtrees_non_root(
+        &subtree_cv(left_len, left),
+        &subtree_cv(len - left_len, right),
+        Mode::Hash,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn root_matches_plain_blake3() {
+        let g = GROUP_LEN as usize;
+        for len in [0, 1, 1024, g, g + 1, 3 * g, 5 * g + 123] {
+            let data = sample(len);
+            let outboard = Outboard::from_reader(&data[..], len as u64).unwrap();
+            assert_eq!(
+                outboard.root(),
+                blake3::hash(&data).to_hex().as_str(),
+                "{len}"
+            );
+            let parsed =
+                Outboard::from_parts(len as u64, &outboard.root(), &outboard.to_bytes()).unwrap();
+            assert_eq!(parsed, outboard);
+
+            // fatias de tamanho arbitrário dão a mesma árvore
+            let mut builder = OutboardBuilder::default();
+            for piece in data.chunks(1000) {
+                builder.update(piece);
+            }
+            assert_eq!(builder.finish(), outboard);
+        }
+    }
+
+    #[test]
+    fn ranges_are_checked_against_the_root() {
+        let g = GROUP_LEN as usize;
+        let mut data = sample(5 * g + 123);
+        let outboard = Outboard::from_reader(&data[..], data.len() as u64).unwrap();
+
+        // faixa desalinhada: só as folhas inteiras dentro dela contam
+        assert_eq!(
+            outboard.verify_range(100, &data[100..3 * g + 5]).unwrap(),
+            2 * GROUP_LEN
+        );
+        assert_eq!(
+            outboard.verify_range(5 * g as u64, &data[5 * g..]).unwrap(),
+            123
+        );
+
+        data[2 * g + 10] ^= 1;
+        assert!(outboard.verify_range(g as u64, &data[g..3 * g]).is_err());
+
+        let mut forged = outboard.to_bytes();
+        forged[0] ^= 1;
+        assert!(Outboard::from_parts(outboard.size(), &outboard.root(), &forged).is_err());
+    }
+}
@@ -1,12 +1,16 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
 
 use parking_lot::Mutex;
-use quinn::{ClientConfig, Endpoint};
+use quinn::{ClientConfig, Connection, Endpoint};
 use std::sync::Arc;
 
+use super::transport::{QuicTransport, SharedTransport};
+
 #[derive(Clone, Default)]
 pub struct QuicManager {
     endpoint: Arc<Mutex<Option<Endpoint>>>,
+    connections: Arc<Mutex<HashMap<String, Connection>>>,
 }
 
 impl QuicManager {
@@ -20,6 +24,24 @@ impl QuicManager {
         *self.endpoint.lock() = Some(endpoint.clone());
         Ok(endpoint)
     }
+
+    fn connection(&self, remote_addr: &str) -> anyhow::Result<Connection> {
+        self.connections
+            .lock()
+            .get(remote_addr)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("nenhuma conexão QUIC com {remote_addr}"))
+    }
+
+    pub async fn open_transport(&self, remote_addr: &str) -> anyhow::Result<SharedTransport> {
+        let (send, recv) = self.connection(remote_addr)?.open_bi().await?;
+        Ok(Arc::new(QuicTransport::new(send, recv)))
+    }
+
+    pub async fn accept_transport(&self, remote_addr: &str) -> anyhow::Result<SharedTransport> {
+        let (send, recv) = self.connection(remote_addr)?.accept_bi().await?;
+        Ok(Arc::new(QuicTransport::new(send, recv)))
+    }
 }
 
 #[tauri::command]
@@ -32,11 +54,14 @@ pub async fn quic_start(
         .ensure_endpoint()
         .await
         .map_err(|e| format!("erro ao criar endpoint QUIC: {e}"))?;
-    let addr: SocketAddr = remote_addr.parse::<SocketAddr>().map_err(|e| e.to_string())?;
+    let addr: SocketAddr = remote_addr
+        .parse::<SocketAddr>()
+        .map_err(|e| e.to_string())?;
     let connection = endpoint
         .connect(addr, "fluxshare")
         .map_err(|e| e.to_string())?;
-    let _ = connection.await.map_err(|e| e.to_string())?;
+    let connection = connection.await.map_err(|e| e.to_string())?;
     tracing::info!(self_id = %self_id, remote = %remote_addr, "quic_connected");
+    manager.connections.lock().insert(remote_addr, connection);
     Ok(())
 }
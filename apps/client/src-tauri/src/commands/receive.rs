@@ -0,0 +1,1320 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use blake3::Hasher;
+
+use super::chunking::{read_indexed, ChunkIndex};
+use super::compression::decode_chunk;
+use super::crypto::{decrypt_chunk, derive_key, file_key, verify_key_check};
+use super::files::{
+    check_link_target, restore_metadata, safe_relative_path, write_range, ReceiveRoot,
+};
+use super::history::Direction;
+use super::outboard::Outboard;
+use super::quic::QuicManager;
+use super::transfer::{
+    manifest_dir, set_state, update_progress, update_status, FileEntry, FileManifest, FileMetadata,
+    TransferControl, TransferError, TransferManager, TransferState, TransferStatus,
+};
+use super::transport::{
+    connect_inbound, FileHeader, Frame, FrameHeader, PeerTarget, SharedTransport,
+};
+use super::webrtc::WebRTCManager;
+
+const STAGING_SUFFIX: &str = ".fluxpart";
+
+struct IncomingFile {
+    key: Option<[u8; 32]>,
+    outboard: Option<Outboard>,
+    staging: PathBuf,
+    target: PathBuf,
+    received: u64,
+    size: u64,
+    metadata: FileMetadata,
+}
+
+pub(super) struct AcceptedSession {
+    key: Option<[u8; 32]>,
+    directories: Vec<(PathBuf, FileMetadata)>,
+    symlinks: Vec<(PathBuf, String)>,
+    headers: Vec<FileHeader>,
+}
+
+#[tauri::command]
+pub async fn receive_files(
+    transfer_manager: tauri::State<'_, TransferManager>,
+    webrtc_manager: tauri::State<'_, WebRTCManager>,
+    quic_manager: tauri::State<'_, QuicManager>,
+    session_id: String,
+    peer: PeerTarget,
+    destination: String,
+    password: Option<String>,
+) -> Result<(), TransferError> {
+    let transport = connect_inbound(&peer, &session_id, &webrtc_manager, &quic_manager)
+        .await
+        .map_err(|e| TransferError::Other(format!("falha ao conectar à origem: {e}")))?;
+
+    let control = transfer_manager.register(&session_id, None);
+    transfer_manager.set_status(
+        session_id.clone(),
+        TransferStatus::new(session_id.clone(), &[]),
+    );
+    // senha errada volta para a UI antes de qualquer byte de arquivo
+    let session = match handshake(&session_id, password.as_deref(), &transport).await {
+        Ok(session) => session,
+        Err(err) => {
+            control.finish();
+            set_state(
+                transfer_manager.inner(),
+                &session_id,
+                TransferState::Failed {
+                    reason: err.to_string(),
+                },
+            );
+            transfer_manager.record_outcome(&session_id, Direction::Receive, &peer);
+            return Err(err);
+        }
+    };
+
+    let manager = transfer_manager.inner().clone();
+    let chunk_index = ChunkIndex::new(&manifest_dir(), &peer, Path::new(&destination));
+    tauri::async_runtime::spawn(async move {
+        let result = receive_body(
+            &session_id,
+            &PathBuf::from(destination),
+            session,
+            &manager,
+            &transport,
+            &control,
+            &chunk_index,
+        )
+        .await;
+        control.finish();
+        match result {
+            Ok(()) => {}
+            Err(TransferError::Cancelled) => {
+                set_state(&manager, &session_id, TransferState::Cancelled);
+            }
+            Err(err) => {
+                tracing::error!(?err, "receive failed");
+                set_state(
+                    &manager,
+                    &session_id,
+                    TransferState::Failed {
+                        reason: err.to_string(),
+                    },
+                );
+            }
+        }
+        manager.record_outcome(&session_id, Direction::Receive, &peer);
+    });
+
+    Ok(())
+}
+
+async fn handshake(
+    session_id: &str,
+    password: Option<&str>,
+    transport: &SharedTransport,
+) -> Result<AcceptedSession, TransferError> {
+    match accept_session(session_id, password, transport).await {
+        Ok(session) => {
+            transport
+                .send(Frame::control(FrameHeader::Accepted))
+                .await?;
+            Ok(session)
+        }
+        Err(err) => {
+            let reply = match &err {
+                TransferError::WrongPassword => FrameHeader::KeyRejected,
+                other => FrameHeader::Error {
+                    message: other.to_string(),
+                },
+            };
+            let _ = transport.send(Frame::control(reply)).await;
+            Err(err)
+        }
+    }
+}
+
+async fn accept_session(
+    session_id: &str,
+    password: Option<&str>,
+    transport: &SharedTransport,
+) -> Result<AcceptedSession, TransferError> {
+    let (encrypted, kdf, key_check, directories, symlinks, headers) = match transport.recv().await?
+    {
+        Some(Frame {
+            header:
+                FrameHeader::Session {
+                    session_id: remote_session,
+                    encrypted,
+                    kdf,
+                    key_check,
+                    directories,
+                    symlinks,
+                    files,
+                },
+            ..
+        }) => {
+            if remote_session != session_id {
+                return Err(TransferError::Other(format!(
+                    "sessão inesperada: {remote_session}"
+                )));
+            }
+            (encrypted, kdf, key_check, directories, symlinks, files)
+        }
+        Some(other) => {
+            return Err(TransferError::Other(format!(
+                "esperava cabeçalho de sessão: {:?}",
+                other.header
+            )))
+        }
+        None => {
+            return Err(TransferError::Other(
+                "conexão encerrada antes do cabeçalho da sessão".into(),
+            ))
+        }
+    };
+
+    // nenhum caminho do remetente pode escapar do destino
+    let directories = directories
+        .into_iter()
+        .map(|dir| Ok((safe_relative_path(&dir.path)?, dir.metadata)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let symlinks = symlinks
+        .into_iter()
+        .map(|link| {
+            let path = safe_relative_path(&link.path)?;
+            let target = link.metadata.symlink.context("link sem alvo")?;
+            check_link_target(&path, &target)?;
+            Ok((path, target))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for header in &headers {
+        safe_relative_path(&header.name)?;
+    }
+    // `a -> .` seguido de `a/b -> ..` leva `b` para fora: nada da sessão passa
+    // por um link da própria sessão
+    let names = headers.iter().map(|header| PathBuf::from(&header.name));
+    for path in directories
+        .iter()
+        .map(|(dir, _)| dir.clone())
+        .chain(symlinks.iter().map(|(link, _)| link.clone()))
+        .chain(names)
+    {
+        if let Some((link, _)) = symlinks
+            .iter()
+            .find(|(link, _)| path != *link && path.starts_with(link))
+        {
+            return Err(TransferError::Other(format!(
+                "{} passa pelo link {}",
+                path.display(),
+                link.display()
+            )));
+        }
+    }
+
+    let key = if encrypted {
+        let kdf = kdf.context("sessão cifrada sem parâmetros de derivação")?;
+        let key_check = key_check.context("sessão cifrada sem verificação de chave")?;
+        let key = derive_key(password.unwrap_or(""), &kdf)?;
+        if !verify_key_check(&key, session_id, &key_check) {
+            return Err(TransferError::WrongPassword);
+        }
+        Some(key)
+    } else {
+        None
+    };
+
+    Ok(AcceptedSession {
+        key,
+        directories,
+        symlinks,
+        headers,
+    })
+}
+
+async fn receive_body(
+    session_id: &str,
+    destination: &Path,
+    session: AcceptedSession,
+    manager: &TransferManager,
+    transport: &SharedTransport,
+    control: &TransferControl,
+    chunk_index: &ChunkIndex,
+) -> Result<(), TransferError> {
+    let result = receive_files_into(
+        session_id,
+        destination,
+        session,
+        manager,
+        transport,
+        control,
+        chunk_index,
+    )
+    .await;
+    if let Err(err) = &result {
+        // avisa o remetente para ele não ficar esperando um ack
+        let _ = transport
+            .send(Frame::control(FrameHeader::Error {
+                message: err.to_string(),
+            }))
+            .await;
+    }
+    result.map_err(TransferError::from)
+}
+
+async fn receive_files_into(
+    session_id: &str,
+    destination: &Path,
+    session: AcceptedSession,
+    manager: &TransferManager,
+    transport: &SharedTransport,
+    control: &TransferControl,
+    chunk_index: &ChunkIndex,
+) -> anyhow::Result<()> {
+    let AcceptedSession {
+        key,
+        directories,
+        symlinks,
+        headers,
+    } = session;
+
+    let root = ReceiveRoot::open(destination)?;
+    for (dir, _) in &directories {
+        root.create_dirs(dir)
+            .with_context(|| format!("criar {}", root.join(dir).display()))?;
+    }
+    let mut incoming = HashMap::new();
+    for header in &headers {
+        let mut file = prepare_incoming(session_id, &root, header)?;
+        anyhow::ensure!(
+            incoming
+                .values()
+                .all(|other: &IncomingFile| other.target != file.target),
+            "arquivo repetido na sessão: {}",
+            header.name
+        );
+        if let Some(key) = &key {
+            let salt = header
+                .key_salt
+                .as_deref()
+                .with_context(|| format!("{} sem salt de cifra", header.name))?;
+            file.key = Some(file_key(key, salt)?);
+        }
+        incoming.insert(header.file, file);
+    }
+
+    let entries: Vec<FileEntry> = headers
+        .iter()
+        .map(|header| FileEntry {
+            path: incoming[&header.file].target.to_string_lossy().to_string(),
+            name: header.name.clone(),
+            size: header.size,
+            is_dir: false,
+            checksum: None,
+            metadata: FileMetadata::default(),
+        })
+        .collect();
+    manager.set_status(
+        session_id.to_string(),
+        TransferStatus::new(session_id.to_string(), &entries),
+    );
+
+    let mut total_received = 0u64;
+    loop {
+        control.checkpoint(manager, session_id).await?;
+        let Some(frame) = transport.recv().await? else {
+            anyhow::bail!("conexão encerrada pelo remetente");
+        };
+        match frame.header {
+            FrameHeader::FileStart {
+                file,
+                root: tree_root,
+                chunks,
+            } => {
+                let target = incoming
+                    .get_mut(&file)
+                    .with_context(|| format!("arquivo desconhecido: {file}"))?;
+                target.outboard = Some(Outboard::from_parts(
+                    target.size,
+                    &tree_root,
+                    &frame.payload,
+                )?);
+
+                // chunks iguais a algum já recebido são copiados do disco
+                let indexed = if chunks.is_empty() {
+                    HashMap::new()
+                } else {
+                    chunk_index.load()
+                };
+                let mut have = Vec::new();
+                let mut offset = 0u64;
+                for chunk in &chunks {
+                    if let Some(data) = read_indexed(&indexed, &chunk.hash, chunk.size) {
+                        root.check(&target.staging)?;
+                        write_range(&target.staging, offset, &data)
+                            .with_context(|| format!("gravar {}", target.staging.display()))?;
+                        have.push(chunk.index);
+                    }
+                    offset += chunk.size;
+                }
+                transport
+                    .send(Frame::control(FrameHeader::Have { file, chunks: have }))
+                    .await?;
+            }
+            FrameHeader::Chunk {
+                file,
+                index,
+                offset,
+                hash,
+                encoding,
+            } => {
+                let target = incoming
+                    .get_mut(&file)
+                    .with_context(|| format!("arquivo desconhecido: {file}"))?;
+                set_state(manager, session_id, TransferState::Transferring);
+                let body = match &target.key {
+                    Some(key) => decrypt_chunk(key, index, &frame.payload)?,
+                    None => frame.payload.to_vec(),
+                };
+                // nada que passe do fim do arquivo é aceito na descompressão
+                let limit = target.size.saturating_sub(offset) as usize;
+                let plain = decode_chunk(encoding, body, limit)?;
+                anyhow::ensure!(
+                    blake3::hash(&plain).to_hex().as_str() == hash,
+                    "chunk {index} corrompido em trânsito"
+                );
+                target
+                    .outboard
+                    .as_ref()
+                    .context("chunk recebido antes do FileStart")?
+                    .verify_range(offset, &plain)?;
+                root.check(&target.staging)?;
+                write_range(&target.staging, offset, &plain)
+                    .with_context(|| format!("gravar {}", target.staging.display()))?;
+
+                let bytes = plain.len() as u64;
+                transport
+                    .send(Frame::control(FrameHeader::Ack { file, index, bytes }))
+                    .await?;
+                target.received += bytes;
+                total_received += bytes;
+                update_progress(
+                    manager,
+                    session_id,
+                    &target.target.to_string_lossy(),
+                    bytes,
+                    total_received,
+                );
+            }
+            FrameHeader::FileEnd { file, manifest } => {
+                let target = incoming
+                    .get(&file)
+                    .with_context(|| format!("arquivo desconhecido: {file}"))?;
+                set_state(manager, session_id, TransferState::Verifying);
+                let outboard = target.outboard.as_ref().context("FileEnd sem FileStart")?;
+                root.check(&target.staging)?;
+                if let Err(err) = verify_staged(&target.staging, &manifest, outboard) {
+                    // staging inconsistente: descarta para o reenvio começar do zero
+                    let _ = fs::remove_file(&target.staging);
+                    return Err(err);
+                }
+                let placed = place_verified(&root, &target.staging, &target.target, &manifest)
+                    .with_context(|| format!("mover para {}", target.target.display()))?;
+                // só depois de verificado: um modo só leitura travaria a escrita
+                let restored = root
+                    .check(&placed)
+                    .and_then(|()| restore_metadata(&placed, &target.metadata));
+                if let Err(err) = restored {
+                    tracing::warn!(?err, path = %placed.display(), "metadata restore failed");
+                }
+                if let Err(err) = chunk_index.record(&placed, &manifest.chunks) {
+                    tracing::warn!(?err, "chunk index update failed");
+                }
+                transport
+                    .send(Frame::control(FrameHeader::FileDone { file }))
+                    .await?;
+
+                // chunks retomados não passam pelo fio, mas já estão no staging
+                total_received += target.size.saturating_sub(target.received);
+                let target_path = target.target.to_string_lossy().to_string();
+                update_status(
+                    |status| {
+                        if let Some(p) = status
+                            .file_progress
+                            .iter_mut()
+                            .find(|p| p.path == target_path)
+                        {
+                            p.path = placed.to_string_lossy().to_string();
+                            p.done = true;
+                            p.transferred = p.total;
+                        }
+                        status.transferred_bytes = total_received;
+                    },
+                    manager,
+                    session_id,
+                );
+            }
+            FrameHeader::Done => {
+                restore_tree(&root, &directories, &symlinks);
+                break;
+            }
+            other => anyhow::bail!("frame inesperado: {other:?}"),
+        }
+    }
+
+    set_state(manager, session_id, TransferState::Completed);
+    update_status(
+        |status| {
+            status.transferred_bytes = status.total_bytes;
+            status.rate = 0.0;
+            status.eta_seconds = Some(0.0);
+        },
+        manager,
+        session_id,
+    );
+
+    Ok(())
+}
+
+fn prepare_incoming(
+    session_id: &str,
+    root: &ReceiveRoot,
+    header: &FileHeader,
+) -> anyhow::Result<IncomingFile> {
+    let relative = safe_relative_path(&header.name)?;
+    let target = root.join(&relative);
+    let name = target
+        .file_name()
+        .with_context(|| format!("nome de arquivo inválido: {}", header.name))?
+        .to_string_lossy()
+        .to_string();
+    if let Some(parent) = relative.parent() {
+        root.create_dirs(parent)
+            .with_context(|| format!("criar {}", root.join(parent).display()))?;
+    }
+    Ok(IncomingFile {
+        key: None,
+        outboard: None,
+        staging: target.with_file_name(staging_name(session_id, header.file, &name)),
+        target,
+        received: 0,
+        size: header.size,
+        metadata: header.metadata.clone(),
+    })
+}
+
+/// Staging próprio da sessão e do arquivo: outra sessão (ou outro arquivo de
+/// mesmo nome) nunca escreve no mesmo lugar, e a retomada reencontra o seu.
+fn staging_name(session_id: &str, file: u32, name: &str) -> String {
+    let session = blake3::hash(session_id.as_bytes()).to_hex();
+    format!("{name}.{}-{file}{STAGING_SUFFIX}", &session[..12])
+}
+
+/// Move o staging verificado para o destino sem sobrescrever nada. Um arquivo
+/// idêntico já no lugar (retomada de sessão) fica como está; qualquer outra
+/// coisa com o nome faz o recebido ganhar um nome livre ao lado.
+fn place_verified(
+    root: &ReceiveRoot,
+    staging: &Path,
+    target: &Path,
+    manifest: &FileManifest,
+) -> anyhow::Result<PathBuf> {
+    root.check(staging)?;
+    for attempt in 0..1000 {
+        let candidate = numbered_name(target, attempt);
+        match fs::symlink_metadata(&candidate) {
+            Ok(existing) => {
+                if existing.is_file() && is_identical(&candidate, existing.len(), manifest)? {
+                    fs::remove_file(staging)?;
+                    return Ok(candidate);
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                fs::rename(staging, &candidate)?;
+                if attempt > 0 {
+                    tracing::info!(
+                        target = %target.display(),
+                        placed = %candidate.display(),
+                        "received file renamed to avoid overwrite"
+                    );
+                }
+                return Ok(candidate);
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    anyhow::bail!("nenhum nome livre para {}", target.display())
+}
+
+/// `nome.ext`, `nome (1).ext`, `nome (2).ext`...
+fn numbered_name(target: &Path, attempt: u32) -> PathBuf {
+    if attempt == 0 {
+        return target.to_path_buf();
+    }
+    let stem = target
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match target.extension() {
+        Some(ext) => format!("{stem} ({attempt}).{}", ext.to_string_lossy()),
+        None => format!("{stem} ({attempt})"),
+    };
+    target.with_file_name(name)
+}
+
+fn is_identical(path: &Path, len: u64, manifest: &FileManifest) -> anyhow::Result<bool> {
+    if len != manifest.size {
+        return Ok(false);
+    }
+    let mut hasher = Hasher::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(manifest.root.as_deref() == Some(hasher.finalize().to_hex().as_str()))
+}
+
+// links e metadados de diretório por último: criar arquivos muda o mtime da
+// pasta, e os filhos vêm antes dos pais
+fn restore_tree(
+    root: &ReceiveRoot,
+    directories: &[(PathBuf, FileMetadata)],
+    symlinks: &[(PathBuf, String)],
+) {
+    for (link, target) in symlinks {
+        if let Err(err) = root.create_link(link, target) {
+            tracing::warn!(?err, path = %link.display(), "symlink restore failed");
+        }
+    }
+    for (dir, metadata) in directories.iter().rev() {
+        let path = root.join(dir);
+        let restored = root
+            .check(&path)
+            .and_then(|()| restore_metadata(&path, metadata));
+        if let Err(err) = restored {
+            tracing::warn!(?err, path = %path.display(), "metadata restore failed");
+        }
+    }
+}
+
+fn verify_staged(
+    staging: &Path,
+    manifest: &FileManifest,
+    outboard: &Outboard,
+) -> anyhow::Result<()> {
+    if !staging.exists() {
+        // arquivo vazio nunca recebe chunk
+        File::create(staging)?;
+    }
+    let mut handle = File::open(staging)?;
+    let len = handle.metadata()?.len();
+    anyhow::ensure!(
+        len == manifest.size,
+        "tamanho divergente: {len} de {} bytes",
+        manifest.size
+    );
+
+    let mut chunks = manifest.chunks.clone();
+    chunks.sort_by_key(|c| c.index);
+    let mut hasher = Hasher::new();
+    let mut consumed = 0u64;
+    for chunk in chunks {
+        let mut buffer = vec![0u8; chunk.size as usize];
+        handle
+            .read_exact(&mut buffer)
+            .with_context(|| format!("ler chunk {}", chunk.index))?;
+        anyhow::ensure!(
+            blake3::hash(&buffer).to_hex().as_str() == chunk.hash,
+            "chunk {} não confere com o manifest",
+            chunk.index
+        );
+        hasher.update(&buffer);
+        consumed += chunk.size;
+    }
+    anyhow::ensure!(consumed == manifest.size, "manifest não cobre o arquivo");
+
+    // o hash do arquivo inteiro é a raiz da árvore anunciada no FileStart
+    let root = hasher.finalize().to_hex().to_string();
+    anyhow::ensure!(
+        root == outboard.root() && manifest.root.as_deref() == Some(root.as_str()),
+        "hash final divergente"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::chunking::ChunkingMode;
+    use crate::commands::compression::Compression;
+    use crate::commands::files::{expand_entries, SymlinkPolicy};
+    use crate::commands::transfer::{execute_transfer, SendOptions, TransferControl, TransferJob};
+    use crate::commands::transport::{MemoryTransport, Transport};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    async fn execute_receive(
+        session_id: String,
+        destination: PathBuf,
+        password: Option<String>,
+        manager: TransferManager,
+        transport: SharedTransport,
+        index_dir: PathBuf,
+    ) -> Result<(), TransferError> {
+        let session = handshake(&session_id, password.as_deref(), &transport).await?;
+        let control = TransferControl::new(None);
+        let peer = PeerTarget::Quic {
+            remote_addr: "127.0.0.1:5000".into(),
+        };
+        let chunk_index = ChunkIndex::new(&index_dir, &peer, &destination);
+        receive_body(
+            &session_id,
+            &destination,
+            session,
+            &manager,
+            &transport,
+            &control,
+            &chunk_index,
+        )
+        .await
+    }
+
+    #[test]
+    fn encrypted_roundtrip_lands_in_destination() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let manifest_dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let source = source_dir.path().join("dados.bin");
+        fs::write(&source, &data).unwrap();
+
+        let entry = FileEntry {
+            path: source.to_string_lossy().to_string(),
+            name: "dados.bin".into(),
+            size: data.len() as u64,
+            is_dir: false,
+            checksum: None,
+            metadata: FileMetadata::default(),
+        };
+        let sender = TransferManager::default();
+        sender.set_status(
+            "rx".into(),
+            TransferStatus::new("rx".into(), &[entry.clone()]),
+        );
+        let receiver = TransferManager::default();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let (local, remote) = MemoryTransport::pair();
+            let receiving = tokio::spawn(execute_receive(
+                "rx".into(),
+                dest_dir.path().to_path_buf(),
+                Some("segredo".into()),
+                receiver.clone(),
+                Arc::new(remote),
+                manifest_dir.path().to_path_buf(),
+            ));
+            execute_transfer(
+                TransferJob {
+                    session_id: "rx".into(),
+                    files: vec![entry],
+                    options: SendOptions {
+                        encrypt: true,
+                        password: Some("segredo".into()),
+                        ..Default::default()
+                    },
+                    chunk_size: 64 * 1024,
+                    parallel_chunks: 4,
+                },
+                manifest_dir.path().to_path_buf(),
+                &sender,
+                Arc::new(local),
+                &TransferControl::new(None),
+            )
+            .await
+            .unwrap();
+            receiving.await.unwrap().unwrap();
+        });
+
+        let written = fs::read(dest_dir.path().join("dados.bin")).unwrap();
+        assert_eq!(written, data);
+        let names: Vec<_> = fs::read_dir(dest_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["dados.bin"]);
+        let status = receiver.get_status("rx").unwrap();
+        assert_eq!(status.transferred_bytes, data.len() as u64);
+        assert!(status.file_progress[0].done);
+        assert_eq!(status.state, TransferState::Completed);
+    }
+
+    #[test]
+    fn wrong_password_is_rejected_before_any_chunk() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let source = source_dir.path().join("segredo.txt");
+        fs::write(&source, b"conteudo confidencial").unwrap();
+        let entry = FileEntry {
+            path: source.to_string_lossy().to_string(),
+            name: "segredo.txt".into(),
+            size: 21,
+            is_dir: false,
+            checksum: None,
+            metadata: FileMetadata::default(),
+        };
+        let sender = TransferManager::default();
+        sender.set_status(
+            "pw".into(),
+            TransferStatus::new("pw".into(), &[entry.clone()]),
+        );
+        let receiver = TransferManager::default();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (sent, received) = rt.block_on(async {
+            let (local, remote) = MemoryTransport::pair();
+            let receiving = tokio::spawn(execute_receive(
+                "pw".into(),
+                dest_dir.path().to_path_buf(),
+                Some("errada".into()),
+                receiver.clone(),
+                Arc::new(remote),
+                source_dir.path().to_path_buf(),
+            ));
+            let sent = execute_transfer(
+                TransferJob {
+                    session_id: "pw".into(),
+                    files: vec![entry],
+                    options: SendOptions {
+                        encrypt: true,
+                        password: Some("certa".into()),
+                        ..Default::default()
+                    },
+                    chunk_size: 1024,
+                    parallel_chunks: 4,
+                },
+                source_dir.path().join("manifests"),
+                &sender,
+                Arc::new(local),
+                &TransferControl::new(None),
+            )
+            .await;
+            (sent, receiving.await.unwrap())
+        });
+
+        assert!(matches!(received, Err(TransferError::WrongPassword)));
+        let sent = sent.unwrap_err();
+        assert!(matches!(
+            sent.downcast_ref::<TransferError>(),
+            Some(TransferError::WrongPassword)
+        ));
+        assert_eq!(sender.get_status("pw").unwrap().transferred_bytes, 0);
+        assert!(fs::read_dir(dest_dir.path()).unwrap().next().is_none());
+    }
+
+    // repassa frames entre dois transportes anotando o payload de cada chunk
+    fn relay(from: SharedTransport, to: SharedTransport, wire: Arc<Mutex<Vec<usize>>>) {
+        tokio::spawn(async move {
+            while let Ok(Some(frame)) = from.recv().await {
+                if matches!(frame.header, FrameHeader::Chunk { .. }) {
+                    wire.lock().push(frame.payload.len());
+                }
+                if to.send(frame).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Envia `source` para `destination` e devolve o tamanho no fio de cada
+    /// chunk enviado.
+    fn send_through_relay(
+        session_id: &str,
+        source: &Path,
+        destination: &Path,
+        manifest_dir: &Path,
+        options: SendOptions,
+        chunk_size: u64,
+    ) -> Vec<usize> {
+        let entry = FileEntry {
+            path: source.to_string_lossy().to_string(),
+            name: source.file_name().unwrap().to_string_lossy().to_string(),
+            size: fs::metadata(source).unwrap().len(),
+            is_dir: false,
+            checksum: None,
+            metadata: FileMetadata::default(),
+        };
+        let sender = TransferManager::default();
+        sender.set_status(
+            session_id.into(),
+            TransferStatus::new(session_id.into(), &[entry.clone()]),
+        );
+        let wire = Arc::new(Mutex::new(Vec::new()));
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let (local, relay_in) = MemoryTransport::pair();
+            let (relay_out, remote) = MemoryTransport::pair();
+            let (relay_in, relay_out): (SharedTransport, SharedTransport) =
+                (Arc::new(relay_in), Arc::new(relay_out));
+            relay(relay_in.clone(), relay_out.clone(), wire.clone());
+            relay(relay_out, relay_in, Arc::default());
+
+            let receiving = tokio::spawn(execute_receive(
+                session_id.into(),
+                destination.to_path_buf(),
+                options.password.clone(),
+                TransferManager::default(),
+                Arc::new(remote),
+                manifest_dir.to_path_buf(),
+            ));
+            execute_transfer(
+                TransferJob {
+                    session_id: session_id.into(),
+                    files: vec![entry],
+                    options,
+                    chunk_size,
+                    parallel_chunks: 4,
+                },
+                manifest_dir.to_path_buf(),
+                &sender,
+                Arc::new(local),
+                &TransferControl::new(None),
+            )
+            .await
+            .unwrap();
+            receiving.await.unwrap().unwrap();
+        });
+        let sizes = wire.lock().clone();
+        sizes
+    }
+
+    #[test]
+    fn edited_resend_reuses_chunks_from_earlier_session() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let manifest_dir = tempfile::tempdir().unwrap();
+        let source = source_dir.path().join("planilha.bin");
+        let mut state = 11u64;
+        let original: Vec<u8> = (0..400_000)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 56) as u8
+            })
+            .collect();
+        let mut edited = original.clone();
+        edited.splice(5000..5000, *b"linha nova");
+
+        let send = |session_id: &str, data: &[u8]| {
+            fs::write(&source, data).unwrap();
+            let options = SendOptions {
+                chunking: ChunkingMode::ContentDefined,
+                ..Default::default()
+            };
+            send_through_relay(
+                session_id,
+                &source,
+                dest_dir.path(),
+                manifest_dir.path(),
+                options,
+                16 * 1024,
+            )
+            .len()
+        };
+
+        let first = send("v1", &original);
+        let second = send("v2", &edited);
+        // a versão anterior não é sobrescrita: a editada ganha outro nome
+        assert_eq!(
+            fs::read(dest_dir.path().join("planilha.bin")).unwrap(),
+            original
+        );
+        assert_eq!(
+            fs::read(dest_dir.path().join("planilha (1).bin")).unwrap(),
+            edited
+        );
+        assert!(first > 10, "{first} chunks");
+        // só a vizinhança da edição atravessa o fio de novo
+        assert!(second <= 2, "{second} de {first} chunks reenviados");
+
+        // reenviar o mesmo conteúdo reencontra a cópia que já está lá
+        assert_eq!(send("v3", &edited), 0);
+        let mut names: Vec<_> = fs::read_dir(dest_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["planilha (1).bin", "planilha.bin"]);
+    }
+
+    #[test]
+    fn compressed_chunks_are_restored_before_verification() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let manifest_dir = tempfile::tempdir().unwrap();
+        let source = source_dir.path().join("servidor.log");
+        let text = (0..20_000)
+            .map(|i| format!("{i:06} INFO requisição atendida em {}ms\n", i % 97))
+            .collect::<String>();
+        fs::write(&source, &text).unwrap();
+
+        let wire = send_through_relay(
+            "zstd",
+            &source,
+            dest_dir.path(),
+            manifest_dir.path(),
+            SendOptions {
+                encrypt: true,
+                password: Some("segredo".into()),
+                compression: Compression::Zstd { level: 3 },
+                ..Default::default()
+            },
+            64 * 1024,
+        );
+
+        assert_eq!(
+            fs::read_to_string(dest_dir.path().join("servidor.log")).unwrap(),
+            text
+        );
+        let on_wire: usize = wire.iter().sum();
+        assert!(
+            on_wire * 4 < text.len(),
+            "{on_wire} de {} bytes",
+            text.len()
+        );
+    }
+
+    #[test]
+    fn directory_tree_is_recreated_under_destination() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let manifest_dir = tempfile::tempdir().unwrap();
+        let projeto = source_dir.path().join("projeto");
+        fs::create_dir_all(projeto.join("src").join("bin")).unwrap();
+        fs::create_dir_all(projeto.join("vazio")).unwrap();
+        fs::write(
+            projeto.join("src").join("bin").join("cli.rs"),
+            b"fn main() {}",
+        )
+        .unwrap();
+        fs::write(projeto.join("LEIAME"), b"leia-me").unwrap();
+
+        let files = expand_entries(
+            &[FileEntry {
+                path: projeto.to_string_lossy().to_string(),
+                name: "projeto".into(),
+                size: 0,
+                is_dir: true,
+                checksum: None,
+                metadata: FileMetadata::default(),
+            }],
+            SymlinkPolicy::Follow,
+        )
+        .unwrap();
+        let sender = TransferManager::default();
+        sender.set_status(
+            "arvore".into(),
+            TransferStatus::new("arvore".into(), &files),
+        );
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let (local, remote) = MemoryTransport::pair();
+            let receiving = tokio::spawn(execute_receive(
+                "arvore".into(),
+                dest_dir.path().to_path_buf(),
+                None,
+                TransferManager::default(),
+                Arc::new(remote),
+                manifest_dir.path().to_path_buf(),
+            ));
+            execute_transfer(
+                TransferJob {
+                    session_id: "arvore".into(),
+                    files,
+                    options: SendOptions::default(),
+                    chunk_size: 1024,
+                    parallel_chunks: 4,
+                },
+                manifest_dir.path().to_path_buf(),
+                &sender,
+                Arc::new(local),
+                &TransferControl::new(None),
+            )
+            .await
+            .unwrap();
+            receiving.await.unwrap().unwrap();
+        });
+
+        let root = dest_dir.path().join("projeto");
+        assert_eq!(
+            fs::read(root.join("src").join("bin").join("cli.rs")).unwrap(),
+            b"fn main() {}"
+        );
+        assert_eq!(fs::read(root.join("LEIAME")).unwrap(), b"leia-me");
+        assert!(root.join("vazio").is_dir());
+        assert_eq!(sender.get_status("arvore").unwrap().total_bytes, 19);
+    }
+
+    #[test]
+    fn traversal_in_session_header_is_refused() {
+        let base = tempfile::tempdir().unwrap();
+        let dest = base.path().join("destino");
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (received, reply) = rt.block_on(async {
+            let (local, remote) = MemoryTransport::pair();
+            let receiving = tokio::spawn(execute_receive(
+                "fuga".into(),
+                dest.clone(),
+                None,
+                TransferManager::default(),
+                Arc::new(remote),
+                base.path().to_path_buf(),
+            ));
+            local
+                .send(Frame::control(FrameHeader::Session {
+                    session_id: "fuga".into(),
+                    encrypted: false,
+                    kdf: None,
+                    key_check: None,
+                    directories: Vec::new(),
+                    symlinks: Vec::new(),
+                    files: vec![FileHeader {
+                        file: 0,
+                        name: "../fora.txt".into(),
+                        size: 3,
+                        key_salt: None,
+                        metadata: FileMetadata::default(),
+                    }],
+                }))
+                .await
+                .unwrap();
+            let reply = local.recv().await.unwrap().unwrap().header;
+            (receiving.await.unwrap(), reply)
+        });
+
+        assert!(received.is_err());
+        assert!(matches!(reply, FrameHeader::Error { .. }));
+        assert!(!base.path().join("fora.txt").exists());
+        assert!(!dest.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn metadata_and_links_are_restored_after_verification() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, SystemTime};
+
+        let source_dir = tempfile::tempdir().unwrap();
+        let dest_dir = tempfile::tempdir().unwrap();
+        let manifest_dir = tempfile::tempdir().unwrap();
+        let pasta = source_dir.path().join("pasta");
+        fs::create_dir_all(&pasta).unwrap();
+        let script = pasta.join("rodar.sh");
+        fs::write(&script, b"#!/bin/sh\necho oi\n").unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&script)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o750)).unwrap();
+        std::os::unix::fs::symlink("rodar.sh", pasta.join("atalho")).unwrap();
+
+        let files = expand_entries(
+            &[FileEntry {
+                path: pasta.to_string_lossy().to_string(),
+                name: "pasta".into(),
+                size: 0,
+                is_dir: true,
+                checksum: None,
+                metadata: FileMetadata::default(),
+            }],
+            SymlinkPolicy::Preserve,
+        )
+        .unwrap();
+        let sender = TransferManager::default();
+        sender.set_status("meta".into(), TransferStatus::new("meta".into(), &files));
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let (local, remote) = MemoryTransport::pair();
+            let receiving = tokio::spawn(execute_receive(
+                "meta".into(),
+                dest_dir.path().to_path_buf(),
+                None,
+                TransferManager::default(),
+                Arc::new(remote),
+                manifest_dir.path().to_path_buf(),
+            ));
+            execute_transfer(
+                TransferJob {
+                    session_id: "meta".into(),
+                    files,
+                    options: SendOptions::default(),
+                    chunk_size: 1024,
+                    parallel_chunks: 4,
+                },
+                manifest_dir.path().to_path_buf(),
+                &sender,
+                Arc::new(local),
+                &TransferControl::new(None),
+            )
+            .await
+            .unwrap();
+            receiving.await.unwrap().unwrap();
+        });
+
+        let received = dest_dir.path().join("pasta");
+        let metadata = fs::metadata(received.join("rodar.sh")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o750);
+        assert_eq!(metadata.modified().unwrap(), modified);
+        assert_eq!(
+            fs::read_link(received.join("atalho")).unwrap(),
+            Path::new("rodar.sh")
+        );
+    }
+
+    /// Sessão só com links, montada à mão como um remetente malicioso faria.
+    async fn send_links(
+        dest: &Path,
+        index_dir: &Path,
+        session_id: &str,
+        links: &[(&str, &str)],
+        files: Vec<FileHeader>,
+    ) -> (Result<(), TransferError>, FrameHeader) {
+        let (local, remote) = MemoryTransport::pair();
+        let receiving = tokio::spawn(execute_receive(
+            session_id.into(),
+            dest.to_path_buf(),
+            None,
+            TransferManager::default(),
+            Arc::new(remote),
+            index_dir.to_path_buf(),
+        ));
+        let link = |(path, target): &(&str, &str)| crate::commands::transport::EntryHeader {
+            path: path.to_string(),
+            metadata: FileMetadata {
+                symlink: Some(target.to_string()),
+                ..Default::default()
+            },
+        };
+        local
+            .send(Frame::control(FrameHeader::Session {
+                session_id: session_id.into(),
+                encrypted: false,
+                kdf: None,
+                key_check: None,
+                directories: Vec::new(),
+                symlinks: links.iter().map(link).collect(),
+                files,
+            }))
+            .await
+            .unwrap();
+        let reply = local.recv().await.unwrap().unwrap().header;
+        if matches!(reply, FrameHeader::Accepted) {
+            local.send(Frame::control(FrameHeader::Done)).await.unwrap();
+        }
+        (receiving.await.unwrap(), reply)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn chained_links_cannot_reach_outside_destination() {
+        let base = tempfile::tempdir().unwrap();
+        let dest = base.path().join("destino");
+        let outside = base.path().join("c");
+        fs::write(&outside, b"original").unwrap();
+        let file_header = |name: &str| FileHeader {
+            file: 0,
+            name: name.into(),
+            size: 3,
+            key_salt: None,
+            metadata: FileMetadata::default(),
+        };
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            // tudo numa sessão: `a/b` passa pelo link `a`
+            let (received, reply) = send_links(
+                &dest,
+                base.path(),
+                "cadeia",
+                &[("a", "."), ("a/b", "..")],
+                vec![file_header("b/c")],
+            )
+            .await;
+            assert!(received.is_err());
+            assert!(matches!(reply, FrameHeader::Error { .. }));
+            assert!(fs::symlink_metadata(dest.join("a")).is_err());
+
+            // em sessões separadas: `a -> .` fica, mas `a/b` não é criado por cima dele
+            let (received, _) = send_links(&dest, base.path(), "um", &[("a", ".")], vec![]).await;
+            received.unwrap();
+            let (received, _) =
+                send_links(&dest, base.path(), "dois", &[("a/b", "..")], vec![]).await;
+            received.unwrap();
+            assert!(fs::symlink_metadata(dest.join("b")).is_err());
+        });
+
+        // um link de fora que já estava no destino não é atravessado
+        std::os::unix::fs::symlink(base.path(), dest.join("x")).unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        let source = source_dir.path().join("c");
+        fs::write(&source, b"novo").unwrap();
+        let send = |name: &str| {
+            let entry = FileEntry {
+                path: source.to_string_lossy().to_string(),
+                name: name.into(),
+                size: 4,
+                is_dir: false,
+                checksum: None,
+                metadata: FileMetadata::default(),
+            };
+            let sender = TransferManager::default();
+            sender.set_status(
+                name.into(),
+                TransferStatus::new(name.into(), &[entry.clone()]),
+            );
+            rt.block_on(async {
+                let (local, remote) = MemoryTransport::pair();
+                let receiving = tokio::spawn(execute_receive(
+                    name.into(),
+                    dest.clone(),
+                    None,
+                    TransferManager::default(),
+                    Arc::new(remote),
+                    base.path().to_path_buf(),
+                ));
+                let sent = execute_transfer(
+                    TransferJob {
+                        session_id: name.into(),
+                        files: vec![entry],
+                        options: SendOptions::default(),
+                        chunk_size: 1024,
+                        parallel_chunks: 4,
+                    },
+                    source_dir.path().join("manifests"),
+                    &sender,
+                    Arc::new(local),
+                    &TransferControl::new(None),
+                )
+                .await;
+                (sent, receiving.await.unwrap())
+            })
+        };
+        let (_, received) = send("x/c");
+        assert!(received.is_err());
+        let (sent, received) = send("b/c");
+        sent.unwrap();
+        received.unwrap();
+
+        assert_eq!(fs::read(&outside).unwrap(), b"original");
+        assert_eq!(fs::read(dest.join("b").join("c")).unwrap(), b"novo");
+        assert!(fs::symlink_metadata(dest.join("b")).unwrap().is_dir());
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+const DEFAULT_MAX_CONCURRENT: usize = 3;
+
+/// Fila de envios: no máximo `max_concurrent` sessões rodam ao mesmo tempo,
+/// as demais esperam por prioridade (maior primeiro) e, empatadas, por ordem
+/// de chegada.
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<Mutex<Queue>>,
+}
+
+struct Queue {
+    max_concurrent: usize,
+    next_ticket: u64,
+    running: HashSet<u64>,
+    waiting: Vec<Waiting>,
+}
+
+struct Waiting {
+    ticket: u64,
+    session_id: String,
+    priority: i32,
+    wake: oneshot::Sender<()>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Queue {
+                max_concurrent: DEFAULT_MAX_CONCURRENT,
+                next_ticket: 0,
+                running: HashSet::new(),
+                waiting: Vec::new(),
+            })),
+        }
+    }
+}
+
+/// Vaga na fila; soltá-la (fim do envio ou desistência) libera a próxima.
+pub(super) struct Slot {
+    scheduler: Scheduler,
+    ticket: u64,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.scheduler.release(self.ticket);
+    }
+}
+
+impl Scheduler {
+    /// Entra na fila. Sem receptor, a vaga já é da sessão; com ele, a vaga
+    /// chega quando o receptor resolver.
+    pub(super) fn enqueue(
+        &self,
+        session_id: &str,
+        priority: i32,
+    ) -> (Slot, Option<oneshot::Receiver<()>>) {
+        let mut queue = self.inner.lock();
+        let ticket = queue.next_ticket;
+        queue.next_ticket += 1;
+        let slot = Slot {
+            scheduler: self.clone(),
+            ticket,
+        };
+        if queue.waiting.is_empty() && queue.running.len() < queue.max_concurrent {
+            queue.running.insert(ticket);
+            return (slot, None);
+        }
+        let (wake, admitted) = oneshot::channel();
+        // depois de todos com prioridade igual ou maior: FIFO dentro do nível
+        let position = queue
+            .waiting
+            .iter()
+            .position(|w| w.priority < priority)
+            .unwrap_or(queue.waiting.len());
+        queue.waiting.insert(
+            position,
+            Waiting {
+                ticket,
+                session_id: session_id.to_string(),
+                priority,
+                wake,
+            },
+        );
+        (slot, Some(admitted))
+    }
+
+    pub fn set_max_concurrent(&self, max: usize) {
+        let mut queue = self.inner.lock();
+        queue.max_concurrent = max.max(1);
+        queue.admit();
+    }
+
+    /// Muda a prioridade de uma sessão na fila; `false` se ela não está esperando.
+    pub fn set_priority(&self, session_id: &str, priority: i32) -> bool {
+        let mut queue = self.inner.lock();
+        let Some(index) = queue
+            .waiting
+            .iter()
+            .position(|w| w.session_id == session_id)
+        else {
+            return false;
+        };
+        let mut entry = queue.waiting.remove(index);
+        entry.priority = priority;
+        let position = queue
+            .waiting
+            .iter()
+            .position(|w| w.priority < priority)
+            .unwrap_or(queue.waiting.len());
+        queue.waiting.insert(position, entry);
+        true
+    }
+
+    /// Põe as sessões listadas na frente, na ordem dada; as outras seguem
+    /// atrás na ordem em que estavam.
+    pub fn reorder(&self, order: &[String]) {
+        let mut queue = self.inner.lock();
+        let rank = |w: &Waiting| {
+            order
+                .iter()
+                .position(|id| *id == w.session_id)
+                .unwrap_or(order.len())
+        };
+        queue.waiting.sort_by_key(rank);
+    }
+
+    /// Sessões esperando, da próxima a sair para a última.
+    pub fn queued(&self) -> Vec<String> {
+        self.inner
+            .lock()
+            .waiting
+            .iter()
+            .map(|w| w.session_id.clone())
+            .collect()
+    }
+
+    fn release(&self, ticket: u64) {
+        let mut queue = self.inner.lock();
+        if !queue.running.remove(&ticket) {
+            queue.waiting.retain(|w| w.ticket != ticket);
+        }
+        queue.admit();
+    }
+}
+
+impl Queue {
+    fn admit(&mut self) {
+        while self.running.len() < self.max_concurrent && !self.waiting.is_empty() {
+            let next = self.waiting.remove(0);
+            if next.wake.send(()).is_ok() {
+                self.running.insert(next.ticket);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_runs_by_priority_then_arrival() {
+        let scheduler = Scheduler::default();
+        scheduler.set_max_concurrent(1);
+        let (first, wait) = scheduler.enqueue("a", 0);
+        assert!(wait.is_none());
+
+        let mut waiting: Vec<_> = [("b", 0), ("c", 0), ("d", 5), ("e", 0)]
+            .into_iter()
+            .map(|(id, priority)| {
+                let (slot, wait) = scheduler.enqueue(id, priority);
+                (id, slot, wait.unwrap())
+            })
+            .collect();
+        assert_eq!(scheduler.queued(), ["d", "b", "c", "e"]);
+
+        assert!(scheduler.set_priority("e", 1));
+        scheduler.reorder(&["c".to_string()]);
+        assert_eq!(scheduler.queued(), ["c", "d", "e", "b"]);
+
+        // desistir na fila não consome vaga
+        let b = waiting.iter().position(|(id, ..)| *id == "b").unwrap();
+        drop(waiting.remove(b));
+        assert_eq!(scheduler.queued(), ["c", "d", "e"]);
+
+        drop(first);
+        let admitted: Vec<&str> = waiting
+            .iter_mut()
+            .filter_map(|(id, _, wait)| wait.try_recv().is_ok().then_some(*id))
+            .collect();
+        assert_eq!(admitted, ["c"]);
+
+        scheduler.set_max_concurrent(3);
+        assert!(scheduler.queued().is_empty());
+    }
+}
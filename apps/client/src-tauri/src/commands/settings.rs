@@ -5,12 +5,34 @@ use std::sync::Arc;
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
+use super::bandwidth::BandwidthManager;
+use super::transfer::TransferManager;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
     pub chunk_size: u64,
     pub parallel_chunks: u32,
     pub ice_timeout_ms: u64,
     pub cloudflared_path: String,
+    /// Dias que o histórico e os manifestos parados são mantidos; 0 guarda tudo.
+    #[serde(default = "default_history_retention_days")]
+    pub history_retention_days: u32,
+    /// Teto de envio somando todas as sessões, em bytes/s; 0 = sem limite.
+    #[serde(default)]
+    pub global_rate_limit: u64,
+    /// Teto padrão de cada sessão (e do host HTTP), em bytes/s; 0 = sem limite.
+    #[serde(default)]
+    pub session_rate_limit: u64,
+    #[serde(default = "default_max_concurrent_sessions")]
+    pub max_concurrent_sessions: u32,
+}
+
+fn default_max_concurrent_sessions() -> u32 {
+    3
+}
+
+fn default_history_retention_days() -> u32 {
+    30
 }
 
 impl Default for Settings {
@@ -20,6 +42,10 @@ impl Default for Settings {
             parallel_chunks: 4,
             ice_timeout_ms: 30_000,
             cloudflared_path: "cloudflared".into(),
+            history_retention_days: default_history_retention_days(),
+            global_rate_limit: 0,
+            session_rate_limit: 0,
+            max_concurrent_sessions: default_max_concurrent_sessions(),
         }
     }
 }
@@ -88,7 +114,16 @@ pub fn get_settings(manager: tauri::State<'_, SettingsManager>) -> Result<Settin
 #[tauri::command]
 pub fn set_settings(
     manager: tauri::State<'_, SettingsManager>,
+    bandwidth: tauri::State<'_, BandwidthManager>,
+    transfer_manager: tauri::State<'_, TransferManager>,
     settings: Settings,
 ) -> Result<(), String> {
-    manager.update(settings).map_err(|e| e.to_string())
+    // grava antes de aplicar: se falhar, nada muda em memória
+    manager
+        .update(settings.clone())
+        .map_err(|e| e.to_string())?;
+    // limites valem na hora, inclusive para sessões e downloads em curso
+    bandwidth.configure(&settings);
+    transfer_manager.set_max_concurrent(settings.max_concurrent_sessions as usize);
+    Ok(())
 }
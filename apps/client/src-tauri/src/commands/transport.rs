@@ -0,0 +1,619 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch, Mutex as AsyncMutex};
+use webrtc::data_channel::data_channel_state::RTCDataChannelState;
+use webrtc::data_channel::RTCDataChannel;
+
+use super::compression::ChunkEncoding;
+use super::crypto::KdfParams;
+use super::quic::QuicManager;
+use super::transfer::{ChunkInfo, FileManifest, FileMetadata};
+use super::webrtc::WebRTCManager;
+
+// limite conservador de mensagem SCTP para interoperar com navegadores
+const DATA_CHANNEL_FRAGMENT: usize = 16 * 1024;
+const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+// sessões que o peer começou e que ainda não abrimos; o que elas mandam
+// antes disso fica limitado a um frame cada
+const MAX_WAITING_SESSIONS: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PeerTarget {
+    #[serde(rename_all = "camelCase")]
+    WebRtc { self_id: String, target_id: String },
+    #[serde(rename_all = "camelCase")]
+    Quic { remote_addr: String },
+}
+
+impl PeerTarget {
+    pub fn route(&self) -> &'static str {
+        match self {
+            Self::WebRtc { .. } => "webRtc",
+            Self::Quic { .. } => "quic",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::WebRtc { target_id, .. } => target_id.clone(),
+            Self::Quic { remote_addr } => remote_addr.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FileHeader {
+    pub file: u32,
+    pub name: String,
+    pub size: u64,
+    #[serde(default)]
+    pub key_salt: Option<String>,
+    #[serde(default)]
+    pub metadata: FileMetadata,
+}
+
+/// Diretório ou link da árvore enviada, sem conteúdo próprio.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryHeader {
+    pub path: String,
+    #[serde(default)]
+    pub metadata: FileMetadata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum FrameHeader {
+    #[serde(rename_all = "camelCase")]
+    Session {
+        session_id: String,
+        encrypted: bool,
+        #[serde(default)]
+        kdf: Option<KdfParams>,
+        #[serde(default)]
+        key_check: Option<String>,
+        /// Diretórios relativos a criar, inclusive os vazios.
+        #[serde(default)]
+        directories: Vec<EntryHeader>,
+        /// Links preservados, recriados depois dos arquivos.
+        #[serde(default)]
+        symlinks: Vec<EntryHeader>,
+        files: Vec<FileHeader>,
+    },
+    Accepted,
+    KeyRejected,
+    /// Abre um arquivo; o payload é o outboard da árvore blake3.
+    #[serde(rename_all = "camelCase")]
+    FileStart {
+        file: u32,
+        root: String,
+        #[serde(default)]
+        chunks: Vec<ChunkInfo>,
+    },
+    /// Resposta ao `FileStart`: chunks que o receptor já montou localmente.
+    #[serde(rename_all = "camelCase")]
+    Have {
+        file: u32,
+        chunks: Vec<u64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Chunk {
+        file: u32,
+        index: u64,
+        offset: u64,
+        hash: String,
+        #[serde(default)]
+        encoding: ChunkEncoding,
+    },
+    #[serde(rename_all = "camelCase")]
+    FileEnd {
+        file: u32,
+        manifest: FileManifest,
+    },
+    #[serde(rename_all = "camelCase")]
+    Ack {
+        file: u32,
+        index: u64,
+        bytes: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    FileDone {
+        file: u32,
+    },
+    #[serde(rename_all = "camelCase")]
+    Error {
+        message: String,
+    },
+    Done,
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub header: FrameHeader,
+    pub payload: Bytes,
+}
+
+impl Frame {
+    pub fn control(header: FrameHeader) -> Self {
+        Self {
+            header,
+            payload: Bytes::new(),
+        }
+    }
+
+    pub fn encode(&self) -> anyhow::Result<Bytes> {
+        let header = serde_json::to_vec(&self.header)?;
+        let mut out = BytesMut::with_capacity(4 + header.len() + self.payload.len());
+        out.put_u32(header.len() as u32);
+        out.put_slice(&header);
+        out.put_slice(&self.payload);
+        Ok(out.freeze())
+    }
+
+    pub fn decode(mut bytes: Bytes) -> anyhow::Result<Self> {
+        anyhow::ensure!(bytes.len() >= 4, "frame truncado");
+        let header_len = bytes.get_u32() as usize;
+        anyhow::ensure!(bytes.len() >= header_len, "cabeçalho de frame truncado");
+        let header_bytes = bytes.split_to(header_len);
+        let header =
+            serde_json::from_slice(&header_bytes).context("cabeçalho de frame inválido")?;
+        Ok(Self {
+            header,
+            payload: bytes,
+        })
+    }
+}
+
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, frame: Frame) -> anyhow::Result<()>;
+    /// `None` quando o lado remoto encerrou o canal.
+    async fn recv(&self) -> anyhow::Result<Option<Frame>>;
+}
+
+pub type SharedTransport = Arc<dyn Transport>;
+
+pub struct QuicTransport {
+    send: AsyncMutex<quinn::SendStream>,
+    recv: AsyncMutex<quinn::RecvStream>,
+}
+
+impl QuicTransport {
+    pub fn new(send: quinn::SendStream, recv: quinn::RecvStream) -> Self {
+        Self {
+            send: AsyncMutex::new(send),
+            recv: AsyncMutex::new(recv),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for QuicTransport {
+    async fn send(&self, frame: Frame) -> anyhow::Result<()> {
+        let encoded = frame.encode()?;
+        let mut stream = self.send.lock().await;
+        stream
+            .write_all(&(encoded.len() as u32).to_be_bytes())
+            .await?;
+        stream.write_all(&encoded).await?;
+        Ok(())
+    }
+
+    async fn recv(&self) -> anyhow::Result<Option<Frame>> {
+        let mut stream = self.recv.lock().await;
+        let mut len = [0u8; 4];
+        match stream.read_exact(&mut len).await {
+            Ok(()) => {}
+            Err(quinn::ReadExactError::FinishedEarly) => return Ok(None),
+            Err(error) => return Err(error.into()),
+        }
+        let len = u32::from_be_bytes(len) as usize;
+        anyhow::ensure!(len <= MAX_FRAME_LEN, "frame QUIC grande demais: {len}");
+        let mut buffer = vec![0u8; len];
+        stream.read_exact(&mut buffer).await?;
+        Frame::decode(Bytes::from(buffer)).map(Some)
+    }
+}
+
+/// Data channel de um peer, dividido entre as sessões abertas com ele. Cada
+/// mensagem leva o id da sessão na frente, e cada sessão remonta só os seus
+/// fragmentos.
+pub struct WebRtcChannel {
+    channel: Arc<RTCDataChannel>,
+    open: watch::Receiver<bool>,
+    routes: Arc<SessionRoutes>,
+}
+
+impl WebRtcChannel {
+    pub fn new(
+        channel: Arc<RTCDataChannel>,
+        open: watch::Receiver<bool>,
+        mut incoming: mpsc::UnboundedReceiver<Bytes>,
+    ) -> Self {
+        let routes = Arc::new(SessionRoutes::default());
+        let demux = routes.clone();
+        tokio::spawn(async move {
+            while let Some(message) = incoming.recv().await {
+                if let Err(err) = demux.route(message) {
+                    tracing::warn!(?err, "datachannel_message_dropped");
+                }
+            }
+            demux.close();
+        });
+        Self {
+            channel,
+            open,
+            routes,
+        }
+    }
+
+    /// Transporte exclusivo de `session_id` sobre este data channel.
+    pub fn session(self: &Arc<Self>, session_id: &str) -> anyhow::Result<SharedTransport> {
+        anyhow::ensure!(
+            !session_id.is_empty() && session_id.len() <= u8::MAX as usize,
+            "id de sessão inválido para o data channel"
+        );
+        let incoming = self.routes.claim(session_id)?;
+        Ok(Arc::new(WebRtcTransport {
+            link: self.clone(),
+            session_id: session_id.to_string(),
+            sending: AsyncMutex::new(()),
+            incoming: AsyncMutex::new(incoming),
+            pending: AsyncMutex::new(BytesMut::new()),
+        }))
+    }
+
+    async fn wait_open(&self) -> anyhow::Result<()> {
+        let mut open = self.open.clone();
+        while !*open.borrow() {
+            if self.channel.ready_state() == RTCDataChannelState::Open {
+                return Ok(());
+            }
+            open.changed()
+                .await
+                .context("data channel fechado antes de abrir")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct SessionRoutes {
+    inner: Mutex<RouteTable>,
+}
+
+#[derive(Default)]
+struct RouteTable {
+    closed: bool,
+    routes: HashMap<String, Route>,
+    // sessões já encerradas aqui: fragmentos atrasados delas são descartados
+    released: HashSet<String>,
+}
+
+enum Route {
+    /// A sessão local ainda não abriu; o que chegar antes espera aqui.
+    Waiting {
+        queued: Vec<Bytes>,
+        bytes: usize,
+    },
+    Open(mpsc::UnboundedSender<Bytes>),
+}
+
+impl SessionRoutes {
+    fn claim(&self, session_id: &str) -> anyhow::Result<mpsc::UnboundedReceiver<Bytes>> {
+        let mut table = self.inner.lock();
+        anyhow::ensure!(!table.closed, "data channel encerrado");
+        let (tx, rx) = mpsc::unbounded_channel();
+        match table.routes.remove(session_id) {
+            Some(Route::Open(open)) => {
+                table
+                    .routes
+                    .insert(session_id.to_string(), Route::Open(open));
+                anyhow::bail!("sessão {session_id} já usa este data channel");
+            }
+            Some(Route::Waiting { queued, .. }) => {
+                for message in queued {
+                    let _ = tx.send(message);
+                }
+            }
+            None => {}
+        }
+        // retomar reabre a sessão com o mesmo id
+        table.released.remove(session_id);
+        table.routes.insert(session_id.to_string(), Route::Open(tx));
+        Ok(rx)
+    }
+
+    fn release(&self, session_id: &str) {
+        let mut table = self.inner.lock();
+        table.routes.remove(session_id);
+        table.released.insert(session_id.to_string());
+    }
+
+    /// Separa o id da sessão e repassa o resto (flag + fragmento) para ela.
+    fn route(&self, mut message: Bytes) -> anyhow::Result<()> {
+        anyhow::ensure!(!message.is_empty(), "mensagem vazia no data channel");
+        let tag_len = message.get_u8() as usize;
+        anyhow::ensure!(message.len() > tag_len, "mensagem truncada no data channel");
+        let tag = message.split_to(tag_len);
+        let session_id = std::str::from_utf8(&tag).context("id de sessão inválido")?;
+        let mut table = self.inner.lock();
+        if table.closed || table.released.contains(session_id) {
+            return Ok(());
+        }
+        let waiting = table
+            .routes
+            .values()
+            .filter(|route| matches!(route, Route::Waiting { .. }))
+            .count();
+        match table.routes.get_mut(session_id) {
+            Some(Route::Open(tx)) => {
+                let _ = tx.send(message);
+            }
+            Some(Route::Waiting { queued, bytes }) => {
+                *bytes += message.len();
+                if *bytes > MAX_FRAME_LEN {
+                    table.routes.remove(session_id);
+                    table.released.insert(session_id.to_string());
+                    anyhow::bail!("sessão {session_id} mandou dados demais antes de abrir");
+                }
+                queued.push(message);
+            }
+            None => {
+                anyhow::ensure!(
+                    waiting < MAX_WAITING_SESSIONS,
+                    "sessões demais esperando no data channel; {session_id} descartada"
+                );
+                let bytes = message.len();
+                table.routes.insert(
+                    session_id.to_string(),
+                    Route::Waiting {
+                        queued: vec![message],
+                        bytes,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Canal fechado: as sessões abertas recebem `None` em vez de esperar.
+    fn close(&self) {
+        let mut table = self.inner.lock();
+        table.closed = true;
+        table.routes.clear();
+    }
+}
+
+/// Uma sessão sobre o [`WebRtcChannel`] do peer.
+pub struct WebRtcTransport {
+    link: Arc<WebRtcChannel>,
+    session_id: String,
+    // os fragmentos de um frame saem juntos mesmo com envios concorrentes
+    sending: AsyncMutex<()>,
+    incoming: AsyncMutex<mpsc::UnboundedReceiver<Bytes>>,
+    pending: AsyncMutex<BytesMut>,
+}
+
+impl Drop for WebRtcTransport {
+    fn drop(&mut self) {
+        self.link.routes.release(&self.session_id);
+    }
+}
+
+/// Quebra `encoded` nas mensagens do data channel: id da sessão, 1 byte de
+/// flag (1 = ainda há fragmentos, 0 = último) e o fragmento.
+fn fragment(session_id: &str, encoded: &[u8]) -> anyhow::Result<Vec<Bytes>> {
+    let mut pieces = encoded.chunks(DATA_CHANNEL_FRAGMENT).peekable();
+    if pieces.peek().is_none() {
+        anyhow::bail!("frame vazio");
+    }
+    let mut messages = Vec::new();
+    while let Some(piece) = pieces.next() {
+        let mut message = BytesMut::with_capacity(piece.len() + session_id.len() + 2);
+        message.put_u8(session_id.len() as u8);
+        message.put_slice(session_id.as_bytes());
+        message.put_u8(u8::from(pieces.peek().is_some()));
+        message.put_slice(piece);
+        messages.push(message.freeze());
+    }
+    Ok(messages)
+}
+
+#[async_trait]
+impl Transport for WebRtcTransport {
+    async fn send(&self, frame: Frame) -> anyhow::Result<()> {
+        self.link.wait_open().await?;
+        let messages = fragment(&self.session_id, &frame.encode()?)?;
+        let _sending = self.sending.lock().await;
+        for message in messages {
+            self.link
+                .channel
+                .send(&message)
+                .await
+                .context("enviar pelo data channel")?;
+        }
+        Ok(())
+    }
+
+    async fn recv(&self) -> anyhow::Result<Option<Frame>> {
+        let mut incoming = self.incoming.lock().await;
+        let mut pending = self.pending.lock().await;
+        loop {
+            let Some(mut message) = incoming.recv().await else {
+                return Ok(None);
+            };
+            anyhow::ensure!(!message.is_empty(), "mensagem vazia no data channel");
+            let more = message.get_u8() == 1;
+            pending.put_slice(&message);
+            anyhow::ensure!(pending.len() <= MAX_FRAME_LEN, "frame WebRTC grande demais");
+            if !more {
+                let frame = pending.split().freeze();
+                return Frame::decode(frame).map(Some);
+            }
+        }
+    }
+}
+
+pub async fn connect_outbound(
+    target: &PeerTarget,
+    session_id: &str,
+    webrtc: &WebRTCManager,
+    quic: &QuicManager,
+) -> anyhow::Result<SharedTransport> {
+    match target {
+        PeerTarget::WebRtc { self_id, target_id } => webrtc
+            .channel(self_id, target_id)
+            .with_context(|| format!("nenhum data channel ativo para {target_id}"))?
+            .session(session_id),
+        PeerTarget::Quic { remote_addr } => quic.open_transport(remote_addr).await,
+    }
+}
+
+pub async fn connect_inbound(
+    source: &PeerTarget,
+    session_id: &str,
+    webrtc: &WebRTCManager,
+    quic: &QuicManager,
+) -> anyhow::Result<SharedTransport> {
+    match source {
+        PeerTarget::WebRtc { self_id, target_id } => webrtc
+            .channel(self_id, target_id)
+            .with_context(|| format!("nenhum data channel ativo para {target_id}"))?
+            .session(session_id),
+        PeerTarget::Quic { remote_addr } => quic.accept_transport(remote_addr).await,
+    }
+}
+
+#[cfg(test)]
+pub struct MemoryTransport {
+    tx: mpsc::UnboundedSender<Bytes>,
+    rx: AsyncMutex<mpsc::UnboundedReceiver<Bytes>>,
+}
+
+#[cfg(test)]
+impl MemoryTransport {
+    pub fn pair() -> (Self, Self) {
+        let (a_tx, a_rx) = mpsc::unbounded_channel();
+        let (b_tx, b_rx) = mpsc::unbounded_channel();
+        (
+            Self {
+                tx: a_tx,
+                rx: AsyncMutex::new(b_rx),
+            },
+            Self {
+                tx: b_tx,
+                rx: AsyncMutex::new(a_rx),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn send(&self, frame: Frame) -> anyhow::Result<()> {
+        self.tx
+            .send(frame.encode()?)
+            .map_err(|_| anyhow::anyhow!("canal fechado"))
+    }
+
+    async fn recv(&self) -> anyhow::Result<Option<Frame>> {
+        match self.rx.lock().await.recv().await {
+            Some(bytes) => Frame::decode(bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reassemble(rx: &mut mpsc::UnboundedReceiver<Bytes>) -> Frame {
+        let mut pending = BytesMut::new();
+        loop {
+            let mut message = rx.try_recv().unwrap();
+            let more = message.get_u8() == 1;
+            pending.put_slice(&message);
+            if !more {
+                return Frame::decode(pending.freeze()).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn sessions_sharing_a_data_channel_do_not_mix() {
+        let frame = |file| Frame {
+            header: FrameHeader::FileDone { file },
+            payload: Bytes::from(vec![file as u8; DATA_CHANNEL_FRAGMENT * 2 + 7]),
+        };
+        let a = fragment("a", &frame(1).encode().unwrap()).unwrap();
+        let b = fragment("b", &frame(2).encode().unwrap()).unwrap();
+        assert_eq!(a.len(), 3);
+
+        let routes = SessionRoutes::default();
+        let mut rx_a = routes.claim("a").unwrap();
+        // fragmentos intercalados, e "b" chegando antes de a sessão abrir
+        for (x, y) in a.into_iter().zip(b) {
+            routes.route(y).unwrap();
+            routes.route(x).unwrap();
+        }
+        let mut rx_b = routes.claim("b").unwrap();
+        assert!(routes.claim("b").is_err());
+
+        for (rx, file) in [(&mut rx_a, 1), (&mut rx_b, 2)] {
+            let got = reassemble(rx);
+            assert!(matches!(got.header, FrameHeader::FileDone { file: f } if f == file));
+            assert!(got.payload.iter().all(|&byte| byte == file as u8));
+            assert!(rx.try_recv().is_err());
+        }
+
+        // encerrada, "a" não volta a acumular fragmentos atrasados
+        routes.release("a");
+        let late = fragment("a", &frame(1).encode().unwrap()).unwrap();
+        for message in late {
+            routes.route(message).unwrap();
+        }
+        assert!(!routes.inner.lock().routes.contains_key("a"));
+
+        // ids inventados: poucas sessões esperando, e cada uma com um frame no máximo
+        let probe = |id: &str| fragment(id, b"x").unwrap().remove(0);
+        for id in 0..MAX_WAITING_SESSIONS {
+            routes.route(probe(&format!("x{id}"))).unwrap();
+        }
+        assert!(routes.route(probe("demais")).is_err());
+        assert!(!routes.inner.lock().routes.contains_key("demais"));
+        let big = Bytes::from(vec![0u8; DATA_CHANNEL_FRAGMENT]);
+        let flood = |_| {
+            let mut message = BytesMut::from(&probe("x0")[..]);
+            message.put_slice(&big);
+            message.freeze()
+        };
+        let overflow = (0..=MAX_FRAME_LEN / DATA_CHANNEL_FRAGMENT)
+            .map(flood)
+            .map(|message| routes.route(message))
+            .find(Result::is_err);
+        assert!(overflow.is_some());
+        assert!(!routes.inner.lock().routes.contains_key("x0"));
+        routes.route(probe("x0")).unwrap();
+        assert!(!routes.inner.lock().routes.contains_key("x0"));
+
+        // retomar a sessão com o mesmo id volta a receber
+        let mut rx_a = routes.claim("a").unwrap();
+        routes.route(probe("a")).unwrap();
+        assert!(rx_a.try_recv().is_ok());
+
+        routes.close();
+        assert!(rx_a.try_recv().is_err());
+        assert!(routes.claim("c").is_err());
+    }
+}
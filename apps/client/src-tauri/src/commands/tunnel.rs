@@ -1,24 +1,29 @@
 use axum::body::Body;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use std::collections::HashSet;
 use std::fmt::Write as FmtWrite;
 use std::fs;
-use std::io::{BufRead, BufReader, SeekFrom};
-use std::path::PathBuf;
+use std::io::{self, BufRead, BufReader, SeekFrom};
+use std::path::{Path as FsPath, PathBuf};
 use std::process::{Child, Command, Stdio};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::thread::JoinHandle as ThreadJoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, Request, State},
     http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
     response::{Html, IntoResponse, Response},
-    routing::get,
-    Router,
+    routing::{get, post},
+    Form, Router,
 };
-use html_escape::encode_text;
+use html_escape::{encode_double_quoted_attribute, encode_text};
 use parking_lot::Mutex;
 use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tauri::Manager;
 use tokio::sync::oneshot;
 use tokio::time::sleep;
@@ -29,10 +34,18 @@ use tokio::{
 use tokio_util::io::ReaderStream;
 use which::which;
 
+use super::archive::{ZipMethod, ZipPlan, ZipSource};
+use super::bandwidth::{BandwidthManager, HOST_SESSION};
+use super::history::unix_millis;
+use super::host_access::{cookie_value, random_token, HostAccess};
+use super::hosted_dir::{self, DirItem};
+use super::outboard::{Outboard, GROUP_LEN};
+
 const EVENT_TUNNEL_LOG: &str = "fluxshare://tunnel-log"; // LLM-LOCK: event name consumed by frontend listeners
 const EVENT_TUNNEL_STATUS: &str = "fluxshare://tunnel-status"; // LLM-LOCK: status event contract with Admin page tests
 const EVENT_TUNNEL_STOPPED: &str = "tunnel:stopped"; // LLM-LOCK: backend exit notification consumed by frontend logger
 const URL_DETECTION_TIMEOUT: Duration = Duration::from_secs(20);
+const EXPIRY_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 const FILENAME_ENCODE_SET: &AsciiSet = &CONTROLS
     .add(b'\0')
@@ -63,20 +76,182 @@ const FILENAME_ENCODE_SET: &AsciiSet = &CONTROLS
     .add(b'`')
     .add(b'$');
 
+// um segmento de caminho por vez: `/` também é escapado
+const PATH_SEGMENT_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'\\')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
 #[derive(Clone)]
 struct HostedFile {
-    id: u64,
+    // token aleatório: ids sequenciais deixariam enumerar os downloads
+    id: String,
     path: PathBuf,
     name: String,
     size: u64,
+    modified: Option<SystemTime>,
+    // preenchido em segundo plano; até lá os downloads saem sem a raiz
+    outboard: Arc<OnceLock<Outboard>>,
+    expires_at: Option<SystemTime>,
+    max_downloads: Option<u32>,
+    // bytes entregues ou reservados por requisições em curso, compartilhado
+    // entre os clones; o saldo é `max_downloads` cópias do arquivo
+    served: Arc<AtomicU64>,
+}
+
+impl HostedFile {
+    // arquivo vazio ainda custa um por download
+    fn unit(&self) -> u64 {
+        self.size.max(1)
+    }
+
+    fn budget(&self) -> Option<u64> {
+        self.max_downloads
+            .map(|max| u64::from(max).saturating_mul(self.unit()))
+    }
+
+    /// Downloads equivalentes ao que já saiu, contando o que está em curso.
+    fn downloads(&self) -> u32 {
+        let served = self.served.load(Ordering::SeqCst);
+        u32::try_from(served.div_ceil(self.unit())).unwrap_or(u32::MAX)
+    }
+
+    fn is_available(&self, now: SystemTime) -> bool {
+        let expired = self.expires_at.is_some_and(|expires| now >= expires);
+        let exhausted = self
+            .budget()
+            .is_some_and(|budget| self.served.load(Ordering::SeqCst) >= budget);
+        !expired && !exhausted
+    }
+
+    /// Reserva `bytes` do saldo para uma requisição; o que não chegar a ser
+    /// enviado volta quando a reserva cai.
+    fn reserve(&self, now: SystemTime, bytes: u64) -> Option<Reservation> {
+        if self.expires_at.is_some_and(|expires| now >= expires) {
+            return None;
+        }
+        let cost = bytes.max(1);
+        let budget = self.budget();
+        self.served
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |served| {
+                let total = served.checked_add(cost)?;
+                match budget {
+                    Some(budget) if total > budget => None,
+                    _ => Some(total),
+                }
+            })
+            .ok()?;
+        Some(Reservation {
+            served: self.served.clone(),
+            unsent: bytes,
+        })
+    }
+}
+
+/// Parte do saldo de um arquivo presa a uma resposta em curso.
+struct Reservation {
+    served: Arc<AtomicU64>,
+    unsent: u64,
+}
+
+impl Reservation {
+    fn sent(&mut self, bytes: u64) {
+        self.unsent = self.unsent.saturating_sub(bytes);
+    }
+
+    /// Corpo que desconta da reserva o que de fato sai; ao ser descartado
+    /// (fim ou conexão caída) devolve o resto.
+    fn track(mut self, body: Body) -> Body {
+        Body::from_stream(body.into_data_stream().map(move |block| {
+            if let Ok(bytes) = &block {
+                self.sent(bytes.len() as u64);
+            }
+            block
+        }))
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.served.fetch_sub(self.unsent, Ordering::SeqCst);
+    }
+}
+
+/// Pasta hospedada inteira; o conteúdo é lido do disco a cada pedido.
+#[derive(Clone)]
+struct HostedDir {
+    id: String,
+    // canônica: tudo que for servido precisa ficar abaixo dela
+    root: PathBuf,
+    name: String,
+    expires_at: Option<SystemTime>,
+}
+
+impl HostedDir {
+    fn is_available(&self, now: SystemTime) -> bool {
+        self.expires_at.is_none_or(|expires| now < expires)
+    }
+}
+
+/// Arquivos hospedados que vieram da mesma pasta, baixáveis juntos em ZIP.
+#[derive(Clone)]
+struct HostedFolder {
+    id: String,
+    dir: PathBuf,
+    name: String,
+    file_ids: Vec<String>,
+}
+
+/// Mantém a contagem de downloads em andamento enquanto o corpo é enviado.
+struct ActiveDownload(Arc<AtomicUsize>);
+
+impl ActiveDownload {
+    fn start(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for ActiveDownload {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct HostedFileSummary {
-    pub id: u64,
+    pub id: String,
     pub name: String,
     pub size: u64,
+    /// Caminho do download direto, relativo à raiz do servidor.
+    pub path: String,
+    /// Unix ms.
+    pub expires_at: Option<u64>,
+    pub max_downloads: Option<u32>,
+    pub downloads: u32,
+    pub expired: bool,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HostedDirSummary {
+    pub id: String,
+    pub name: String,
+    /// Caminho da listagem navegável, relativo à raiz do servidor.
+    pub path: String,
+    /// Unix ms.
+    pub expires_at: Option<u64>,
+    pub expired: bool,
 }
 
 #[derive(Clone)]
@@ -94,12 +269,62 @@ struct TunnelState {
     server_port: Option<u16>,
     exit_monitor: Option<tauri::async_runtime::JoinHandle<()>>,
     files: Vec<HostedFile>,
-    next_file_id: u64,
+    folders: Vec<HostedFolder>,
+    dirs: Vec<HostedDir>,
+    // com ele, a listagem só existe em `/s/<token>/`
+    index_token: Option<String>,
+    // senha da sessão de host atual; `None` deixa a listagem aberta
+    access: Option<HostAccess>,
+    // muda a cada `start_host`; o vigia de expiração de uma sessão antiga sai
+    host_generation: u64,
+}
+
+impl TunnelState {
+    fn index_path(&self) -> String {
+        match &self.index_token {
+            Some(token) => format!("/s/{token}/"),
+            None => "/".to_string(),
+        }
+    }
+
+    /// O ZIP completo fica ao lado da listagem: com link de sessão, não há
+    /// caminho fixo que entregue todos os arquivos.
+    fn archive_path(&self) -> String {
+        match &self.index_token {
+            Some(token) => format!("/s/{token}/all.zip"),
+            None => "/download/all.zip".to_string(),
+        }
+    }
+
+    /// Links de ZIP mostrados na listagem: tudo e, com mais de uma pasta, cada uma.
+    fn archive_links(&self) -> Vec<(String, String)> {
+        let mut links = vec![("Baixar tudo (.zip)".to_string(), self.archive_path())];
+        if self.folders.len() > 1 {
+            links.extend(self.folders.iter().map(|folder| {
+                (
+                    format!("{}.zip", folder.name),
+                    format!("/download/folder/{}", folder.id),
+                )
+            }));
+        }
+        links
+    }
 }
 
 #[derive(Default, Clone)]
 pub struct TunnelManager {
-    pub(super) inner: Arc<Mutex<TunnelState>>,
+    inner: Arc<Mutex<TunnelState>>,
+    bandwidth: BandwidthManager,
+    active_downloads: Arc<AtomicUsize>,
+}
+
+impl TunnelManager {
+    pub fn with_bandwidth(bandwidth: BandwidthManager) -> Self {
+        Self {
+            bandwidth,
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Serialize, Clone)]
@@ -114,6 +339,7 @@ pub struct TunnelStatus {
     pub url: Option<String>,
     pub local_port: Option<u16>,
     pub hosted_files: Vec<HostedFileSummary>,
+    pub hosted_dirs: Vec<HostedDirSummary>,
 }
 
 #[derive(Serialize, Clone)]
@@ -157,7 +383,10 @@ async fn finalize_tunnel_exit(app: &tauri::AppHandle, manager: &TunnelManager, c
         state.server_port = None;
         state.exit_monitor = None;
         state.files.clear();
-        state.next_file_id = 0;
+        state.folders.clear();
+        state.dirs.clear();
+        state.index_token = None;
+        state.access = None;
         (
             state.log_handles.drain(..).collect::<Vec<_>>(),
             state.server_shutdown.take(),
@@ -273,19 +502,62 @@ fn format_file_size(size: u64) -> String {
     }
 }
 
+fn spawn_outboard(path: PathBuf, size: u64, slot: Arc<OnceLock<Outboard>>) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let outboard = fs::File::open(&path)
+            .and_then(|file| Outboard::from_reader(BufReader::new(file), size));
+        match outboard {
+            Ok(outboard) => {
+                let _ = slot.set(outboard);
+            }
+            Err(error) => tracing::warn!(?error, path = %path.display(), "outboard_hash_failed"),
+        }
+    });
+}
+
 fn summarize_files(files: &[HostedFile]) -> Vec<HostedFileSummary> {
+    let now = SystemTime::now();
     files
         .iter()
         .map(|file| HostedFileSummary {
-            id: file.id,
+            id: file.id.clone(),
             name: file.name.clone(),
             size: file.size,
+            path: format!("/download/{}", file.id),
+            expires_at: file.expires_at.map(unix_millis),
+            max_downloads: file.max_downloads,
+            downloads: file.downloads(),
+            expired: !file.is_available(now),
         })
         .collect()
 }
 
-fn render_index_page(files: &[HostedFileSummary]) -> String {
-    let mut html = String::new();
+fn summarize_dirs(dirs: &[HostedDir]) -> Vec<HostedDirSummary> {
+    let now = SystemTime::now();
+    dirs.iter()
+        .map(|dir| HostedDirSummary {
+            id: dir.id.clone(),
+            name: dir.name.clone(),
+            path: format!("/browse/{}/", dir.id),
+            expires_at: dir.expires_at.map(unix_millis),
+            expired: !dir.is_available(now),
+        })
+        .collect()
+}
+
+/// `/<rota>/<id>/a/b`, com cada segmento escapado.
+fn dir_url(route: &str, id: &str, components: &[&str]) -> String {
+    let mut url = format!("/{route}/{id}/");
+    for (index, component) in components.iter().enumerate() {
+        if index > 0 {
+            url.push('/');
+        }
+        url.extend(utf8_percent_encode(component, PATH_SEGMENT_ENCODE_SET));
+    }
+    url
+}
+
+fn push_page_start(html: &mut String, subtitle: &str) {
     let _ = write!(
         html,
         "<!DOCTYPE html><html lang=\"pt-BR\"><head><meta charset=\"utf-8\" />\
@@ -301,18 +573,49 @@ a{{color:#38bdf8;text-decoration:none;font-weight:600;}}\
 a:hover{{text-decoration:underline;}}\
 .empty{{padding:1.5rem;border-radius:0.75rem;border:1px dashed rgba(148,163,184,0.4);color:#94a3b8;background:rgba(148,163,184,0.08);}}\
 .size{{font-size:0.875rem;color:#cbd5f5;}}\
-</style></head><body><div class=\"container\"><h1>FluxShare</h1><p class=\"subtitle\">Arquivos hospedados via FluxShare.</p>"
+.archives{{display:flex;flex-wrap:wrap;gap:0.75rem;margin-bottom:1.25rem;}}\
+.crumbs{{display:flex;flex-wrap:wrap;gap:0.5rem;margin-bottom:1.25rem;color:#94a3b8;}}\
+</style></head><body><div class=\"container\"><h1>FluxShare</h1><p class=\"subtitle\">{subtitle}</p>",
+        subtitle = encode_text(subtitle),
     );
+}
 
-    if files.is_empty() {
+fn render_index_page(
+    files: &[HostedFileSummary],
+    dirs: &[HostedDirSummary],
+    archives: &[(String, String)],
+) -> String {
+    let mut html = String::new();
+    push_page_start(&mut html, "Arquivos hospedados via FluxShare.");
+
+    let files: Vec<_> = files.iter().filter(|file| !file.expired).collect();
+    let dirs: Vec<_> = dirs.iter().filter(|dir| !dir.expired).collect();
+    if files.is_empty() && dirs.is_empty() {
         html.push_str("<div class=\"empty\">Nenhum arquivo hospedado.</div>");
     } else {
-        html.push_str("<ul>");
+        html.push_str("<div class=\"archives\">");
+        for (label, path) in archives {
+            let _ = write!(
+                html,
+                "<a href=\"{path}\">{label}</a>",
+                path = encode_double_quoted_attribute(path),
+                label = encode_text(label),
+            );
+        }
+        html.push_str("</div><ul>");
+        for dir in dirs {
+            let _ = write!(
+                html,
+                "<li><a href=\"{path}\">{name}/</a><span class=\"size\">pasta</span></li>",
+                path = dir.path,
+                name = encode_text(&dir.name),
+            );
+        }
         for file in files {
             let _ = write!(
                 html,
-                "<li><a href=\"/download/{id}\">{name}</a><span class=\"size\">{size}</span></li>",
-                id = file.id,
+                "<li><a href=\"{path}\">{name}</a><span class=\"size\">{size}</span></li>",
+                path = file.path,
                 name = encode_text(&file.name),
                 size = encode_text(&format_file_size(file.size)),
             );
@@ -324,12 +627,259 @@ a:hover{{text-decoration:underline;}}\
     html
 }
 
-async fn index_handler(State(state): State<ServerState>) -> Html<String> {
-    let summaries = {
+/// Listagem de uma pasta dentro de uma pasta hospedada, com trilha de volta
+/// até a página inicial (se houver) e o ZIP da pasta atual.
+fn render_dir_page(
+    dir: &HostedDir,
+    components: &[&str],
+    items: &[DirItem],
+    home: Option<&str>,
+) -> String {
+    let mut html = String::new();
+    push_page_start(&mut html, &dir.name);
+
+    html.push_str("<nav class=\"crumbs\">");
+    if let Some(home) = home {
+        let _ = write!(
+            html,
+            "<a href=\"{home}\">Início</a><span>/</span>",
+            home = encode_double_quoted_attribute(home),
+        );
+    }
+    let trail = std::iter::once(dir.name.as_str()).chain(components.iter().copied());
+    let depth = components.len();
+    for (index, name) in trail.enumerate() {
+        if index > 0 {
+            html.push_str("<span>/</span>");
+        }
+        if index == depth {
+            let _ = write!(html, "<span>{}</span>", encode_text(name));
+        } else {
+            let _ = write!(
+                html,
+                "<a href=\"{path}\">{name}</a>",
+                path = encode_double_quoted_attribute(&dir_url(
+                    "browse",
+                    &dir.id,
+                    &components[..index]
+                )),
+                name = encode_text(name),
+            );
+        }
+    }
+    let _ = write!(
+        html,
+        "</nav><div class=\"archives\"><a href=\"{path}\">Baixar esta pasta (.zip)</a></div>",
+        path = encode_double_quoted_attribute(&dir_url("zip", &dir.id, components)),
+    );
+
+    if items.is_empty() {
+        html.push_str("<div class=\"empty\">Pasta vazia.</div>");
+    } else {
+        html.push_str("<ul>");
+        for item in items {
+            let child: Vec<&str> = components
+                .iter()
+                .copied()
+                .chain(std::iter::once(item.name.as_str()))
+                .collect();
+            let mut path = dir_url("browse", &dir.id, &child);
+            let (name, size) = if item.is_dir {
+                path.push('/');
+                (format!("{}/", item.name), "pasta".to_string())
+            } else {
+                (item.name.clone(), format_file_size(item.size))
+            };
+            let _ = write!(
+                html,
+                "<li><a href=\"{path}\">{name}</a><span class=\"size\">{size}</span></li>",
+                path = encode_double_quoted_attribute(&path),
+                name = encode_text(&name),
+                size = encode_text(&size),
+            );
+        }
+        html.push_str("</ul>");
+    }
+
+    html.push_str("</div></body></html>");
+    html
+}
+
+fn render_login_page(error: Option<&str>, next: &str) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html><html lang=\"pt-BR\"><head><meta charset=\"utf-8\" />\
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\" />\
+<title>FluxShare</title>\
+<style>body{{font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',sans-serif;background:#0f172a;color:#f8fafc;margin:0;padding:2.5rem;}}\
+.container{{max-width:420px;margin:0 auto;}}\
+h1{{font-size:2rem;margin-bottom:0.5rem;}}\
+p.subtitle{{margin-top:0;margin-bottom:1.5rem;color:#94a3b8;}}\
+form{{display:flex;flex-direction:column;gap:0.75rem;}}\
+input{{padding:0.75rem;border-radius:0.5rem;border:1px solid rgba(148,163,184,0.4);background:rgba(148,163,184,0.12);color:#f8fafc;}}\
+button{{padding:0.75rem;border:0;border-radius:0.5rem;background:#38bdf8;color:#0f172a;font-weight:600;cursor:pointer;}}\
+.error{{color:#f87171;}}\
+</style></head><body><div class=\"container\"><h1>FluxShare</h1><p class=\"subtitle\">Estes arquivos estão protegidos por senha.</p>"
+    );
+    if let Some(error) = error {
+        let _ = write!(html, "<p class=\"error\">{}</p>", encode_text(error));
+    }
+    let _ = write!(
+        html,
+        "<form method=\"post\" action=\"/login\">\
+<input type=\"hidden\" name=\"next\" value=\"{next}\" />\
+<input type=\"password\" name=\"password\" placeholder=\"Senha\" autofocus required />\
+<button type=\"submit\">Entrar</button></form></div></body></html>",
+        next = encode_double_quoted_attribute(next),
+    );
+    html
+}
+
+/// Barra listagem e downloads sem o cookie da sessão de host, se houver senha.
+async fn require_access(
+    State(state): State<ServerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (access, index_path) = {
         let state_guard = state.manager.inner.lock();
-        summarize_files(&state_guard.files)
+        (state_guard.access.clone(), state_guard.index_path())
     };
-    Html(render_index_page(&summaries))
+    let Some(access) = access else {
+        return next.run(request).await;
+    };
+    let allowed = request
+        .headers()
+        .get(header::COOKIE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(cookie_value)
+        .is_some_and(|value| access.verify_cookie(value, SystemTime::now()));
+    if allowed {
+        return next.run(request).await;
+    }
+    let path = request.uri().path();
+    if path == index_path || path == index_path.trim_end_matches('/') {
+        (
+            StatusCode::UNAUTHORIZED,
+            Html(render_login_page(None, &index_path)),
+        )
+            .into_response()
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    password: String,
+    #[serde(default)]
+    next: String,
+}
+
+/// IP de quem chegou pelo túnel; acessos diretos dividem uma chave só.
+fn login_client(headers: &HeaderMap) -> String {
+    headers
+        .get("cf-connecting-ip")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .unwrap_or("local")
+        .to_string()
+}
+
+/// O túnel termina o https e repassa o esquema original nos cabeçalhos.
+fn came_over_https(headers: &HeaderMap) -> bool {
+    let forwarded = headers
+        .get("x-forwarded-proto")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("https"));
+    let visitor = headers
+        .get("cf-visitor")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("\"https\""));
+    forwarded || visitor
+}
+
+async fn login_handler(
+    State(state): State<ServerState>,
+    headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> Response {
+    let (access, index_path) = {
+        let state_guard = state.manager.inner.lock();
+        (state_guard.access.clone(), state_guard.index_path())
+    };
+    let client = login_client(&headers);
+    let cookie = match access.map(|access| {
+        let outcome = access.login(&client, &form.password, Instant::now());
+        (access, outcome)
+    }) {
+        Some((access, Ok(true))) => {
+            Some(access.set_cookie_header(SystemTime::now(), came_over_https(&headers)))
+        }
+        Some((_, Ok(false))) => {
+            tracing::info!(client = %client, "host_login_rejected");
+            return (
+                StatusCode::UNAUTHORIZED,
+                Html(render_login_page(Some("Senha incorreta."), &index_path)),
+            )
+                .into_response();
+        }
+        Some((_, Err(wait))) => {
+            let secs = wait.as_secs_f64().ceil() as u64;
+            tracing::warn!(client = %client, retry_after = secs, "host_login_throttled");
+            let message = format!("Muitas tentativas. Aguarde {secs} s e tente de novo.");
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, secs.to_string())],
+                Html(render_login_page(Some(&message), &index_path)),
+            )
+                .into_response();
+        }
+        None => None,
+    };
+    // só volta para a listagem atual: `next` vem do formulário, não é confiável
+    let location = if form.next == index_path {
+        index_path
+    } else {
+        "/".to_string()
+    };
+    let mut response = StatusCode::SEE_OTHER.into_response();
+    if let Ok(value) = HeaderValue::from_str(&location) {
+        response.headers_mut().insert(header::LOCATION, value);
+    }
+    if let Some(value) = cookie.and_then(|cookie| HeaderValue::from_str(&cookie).ok()) {
+        response.headers_mut().insert(header::SET_COOKIE, value);
+    }
+    response
+}
+
+async fn index_handler(State(state): State<ServerState>) -> Result<Html<String>, StatusCode> {
+    render_index(&state, None)
+}
+
+async fn session_index_handler(
+    State(state): State<ServerState>,
+    Path(token): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    render_index(&state, Some(&token))
+}
+
+/// A listagem mora na raiz ou, com link de sessão, só no caminho do token.
+fn render_index(state: &ServerState, token: Option<&str>) -> Result<Html<String>, StatusCode> {
+    let (summaries, dirs, archives) = {
+        let state_guard = state.manager.inner.lock();
+        if state_guard.index_token.as_deref() != token {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        (
+            summarize_files(&state_guard.files),
+            summarize_dirs(&state_guard.dirs),
+            state_guard.archive_links(),
+        )
+    };
+    Ok(Html(render_index_page(&summaries, &dirs, &archives)))
 }
 
 fn parse_range_header(value: &str, total_size: u64) -> Result<Option<(u64, u64)>, ()> {
@@ -370,9 +920,44 @@ fn parse_range_header(value: &str, total_size: u64) -> Result<Option<(u64, u64)>
     Ok(Some((start, end)))
 }
 
+async fn outboard_handler(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+) -> Result<Response, StatusCode> {
+    let file = {
+        let state_guard = state.manager.inner.lock();
+        state_guard.files.iter().find(|file| file.id == id).cloned()
+    };
+    let file = file.ok_or(StatusCode::NOT_FOUND)?;
+    if !file.is_available(SystemTime::now()) {
+        return Err(StatusCode::GONE);
+    }
+
+    let Some(outboard) = file.outboard.get() else {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from_static("2"));
+        return Ok(response);
+    };
+
+    let mut response = Response::new(Body::from(outboard.to_bytes()));
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    if let Ok(value) = HeaderValue::from_str(&outboard.root()) {
+        headers.insert("x-blake3-root", value);
+    }
+    headers.insert("x-blake3-group-size", HeaderValue::from(GROUP_LEN));
+    Ok(response)
+}
+
 async fn download_handler(
     State(state): State<ServerState>,
-    Path(id): Path<u64>,
+    Path(id): Path<String>,
     headers: HeaderMap,
 ) -> Result<Response, StatusCode> {
     let file = {
@@ -381,34 +966,59 @@ async fn download_handler(
     };
 
     let file = file.ok_or(StatusCode::NOT_FOUND)?;
-    let mut handle = File::open(&file.path)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let range = requested_range(&headers, file.size)?;
 
-    let mut status = StatusCode::OK;
-    let mut start = 0u64;
-    let mut end = if file.size == 0 {
+    // cada requisição, inteira ou em trechos, gasta os bytes que entrega:
+    // retomar não cobra de novo e trechos não escapam do limite
+    let bytes = if file.size == 0 {
         0
     } else {
-        file.size.saturating_sub(1)
+        range.1 - range.0 + 1
     };
+    let reservation = file
+        .reserve(SystemTime::now(), bytes)
+        .ok_or(StatusCode::GONE)?;
+
+    let mut response = stream_file(&state.manager, &file.path, file.size, &file.name, range)
+        .await?
+        .map(|body| reservation.track(body));
+    if let Some(outboard) = file.outboard.get() {
+        if let Ok(value) = HeaderValue::from_str(&outboard.root()) {
+            response.headers_mut().insert("x-blake3-root", value);
+        }
+        response
+            .headers_mut()
+            .insert("x-blake3-group-size", HeaderValue::from(GROUP_LEN));
+    }
+    Ok(response)
+}
 
-    if let Some(range_header) = headers
+/// Trecho pedido pelo cabeçalho `Range`: início, fim inclusivo e se é parcial.
+fn requested_range(headers: &HeaderMap, size: u64) -> Result<(u64, u64, bool), StatusCode> {
+    let whole = (0, size.saturating_sub(1), false);
+    let Some(range_header) = headers
         .get(header::RANGE)
         .and_then(|value| value.to_str().ok())
-    {
-        match parse_range_header(range_header, file.size) {
-            Ok(Some((s, e))) => {
-                start = s;
-                end = e;
-                status = StatusCode::PARTIAL_CONTENT;
-            }
-            Ok(None) => {}
-            Err(_) => return Err(StatusCode::RANGE_NOT_SATISFIABLE),
-        }
+    else {
+        return Ok(whole);
+    };
+    match parse_range_header(range_header, size) {
+        Ok(Some((start, end))) => Ok((start, end, true)),
+        Ok(None) => Ok(whole),
+        Err(_) => Err(StatusCode::RANGE_NOT_SATISFIABLE),
     }
+}
 
-    let bytes_to_read = if file.size == 0 {
+async fn stream_file(
+    manager: &TunnelManager,
+    path: &FsPath,
+    size: u64,
+    name: &str,
+    (start, end, partial): (u64, u64, bool),
+) -> Result<Response, StatusCode> {
+    let mut handle = File::open(path).await.map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let bytes_to_read = if size == 0 {
         0
     } else {
         end.saturating_sub(start).saturating_add(1)
@@ -421,9 +1031,11 @@ async fn download_handler(
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     }
 
-    let stream = ReaderStream::new(handle.take(bytes_to_read));
-    let mut response = Response::new(Body::from_stream(stream));
-    *response.status_mut() = status;
+    let body = throttled_body(manager, ReaderStream::new(handle.take(bytes_to_read)));
+    let mut response = Response::new(body);
+    if partial {
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+    }
 
     if let Ok(value) = HeaderValue::from_str(&bytes_to_read.to_string()) {
         response.headers_mut().insert(header::CONTENT_LENGTH, value);
@@ -432,25 +1044,13 @@ async fn download_handler(
         .headers_mut()
         .insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
 
-    if status == StatusCode::PARTIAL_CONTENT {
-        if let Ok(value) =
-            HeaderValue::from_str(&format!("bytes {start}-{end}/{total}", total = file.size))
-        {
+    if partial {
+        if let Ok(value) = HeaderValue::from_str(&format!("bytes {start}-{end}/{size}")) {
             response.headers_mut().insert(header::CONTENT_RANGE, value);
         }
     }
 
-    let ascii_name = ascii_filename_fallback(&file.name);
-    let encoded_name = utf8_percent_encode(&file.name, FILENAME_ENCODE_SET).to_string();
-    let disposition = format!(
-        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
-        ascii_name, encoded_name
-    );
-    if let Ok(value) = HeaderValue::from_str(&disposition) {
-        response
-            .headers_mut()
-            .insert(header::CONTENT_DISPOSITION, value);
-    }
+    insert_attachment(response.headers_mut(), name);
     response.headers_mut().insert(
         header::CONTENT_TYPE,
         HeaderValue::from_static("application/octet-stream"),
@@ -459,12 +1059,405 @@ async fn download_handler(
     Ok(response)
 }
 
+async fn browse_root_handler(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    browse(&state, &id, "", &headers).await
+}
+
+async fn browse_handler(
+    State(state): State<ServerState>,
+    Path((id, path)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    browse(&state, &id, &path, &headers).await
+}
+
+enum Browsed {
+    Dir(Vec<DirItem>),
+    File(PathBuf, u64),
+}
+
+/// Pasta hospedada disponível com esse id, e a página inicial para a trilha.
+/// Com link de sessão não há página inicial: o token não vai parar no HTML
+/// de quem só recebeu o link da pasta.
+fn find_dir(state: &ServerState, id: &str) -> Result<(HostedDir, Option<String>), StatusCode> {
+    let state_guard = state.manager.inner.lock();
+    let dir = state_guard
+        .dirs
+        .iter()
+        .find(|dir| dir.id == id)
+        .cloned()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if !dir.is_available(SystemTime::now()) {
+        return Err(StatusCode::GONE);
+    }
+    let home = state_guard
+        .index_token
+        .is_none()
+        .then(|| state_guard.index_path());
+    Ok((dir, home))
+}
+
+/// Tudo que sai da raiz, não existe ou não pode ser lido vira 404: quem
+/// tenta escapar não aprende nada sobre o resto do disco.
+fn browse_status(error: &io::Error) -> StatusCode {
+    match error.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+async fn browse(
+    state: &ServerState,
+    id: &str,
+    relative: &str,
+    headers: &HeaderMap,
+) -> Result<Response, StatusCode> {
+    let (dir, home) = find_dir(state, id)?;
+    let root = dir.root.clone();
+    let target = relative.to_string();
+    let browsed = tauri::async_runtime::spawn_blocking(move || -> io::Result<Browsed> {
+        let path = hosted_dir::resolve(&root, &target)?;
+        let metadata = fs::metadata(&path)?;
+        if metadata.is_dir() {
+            Ok(Browsed::Dir(hosted_dir::list(&root, &path)?))
+        } else {
+            Ok(Browsed::File(path, metadata.len()))
+        }
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|error| browse_status(&error))?;
+
+    let components: Vec<&str> = relative
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect();
+    match browsed {
+        Browsed::Dir(items) => {
+            Ok(Html(render_dir_page(&dir, &components, &items, home.as_deref())).into_response())
+        }
+        Browsed::File(path, size) => {
+            let name = components.last().copied().unwrap_or(&dir.name);
+            let range = requested_range(headers, size)?;
+            stream_file(&state.manager, &path, size, name, range).await
+        }
+    }
+}
+
+/// Corpo de download sujeito aos limites de banda e contado como em andamento.
+fn throttled_body<S>(manager: &TunnelManager, stream: S) -> Body
+where
+    S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+{
+    // limite consultado a cada bloco: mudar nas configurações vale para downloads em curso
+    let bandwidth = manager.bandwidth.clone();
+    let limiter = bandwidth.session(HOST_SESSION);
+    let active = ActiveDownload::start(&manager.active_downloads);
+    Body::from_stream(stream.then(move |block| {
+        let bandwidth = bandwidth.clone();
+        let limiter = limiter.clone();
+        // vive tanto quanto o corpo da resposta
+        let _active = &active;
+        async move {
+            if let Ok(bytes) = &block {
+                bandwidth.throttle(&limiter, bytes.len() as u64).await;
+            }
+            block
+        }
+    }))
+}
+
+#[derive(Deserialize)]
+struct ArchiveQuery {
+    // `deflate` comprime; o padrão (store) permite mandar o tamanho antes
+    method: Option<String>,
+}
+
+impl ArchiveQuery {
+    fn method(&self) -> ZipMethod {
+        match self.method.as_deref() {
+            Some(method) if method.eq_ignore_ascii_case("deflate") => ZipMethod::Deflate,
+            _ => ZipMethod::Store,
+        }
+    }
+}
+
+async fn archive_handler(
+    State(state): State<ServerState>,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<Response, StatusCode> {
+    serve_full_archive(&state, None, query.method()).await
+}
+
+async fn session_archive_handler(
+    State(state): State<ServerState>,
+    Path(token): Path<String>,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<Response, StatusCode> {
+    serve_full_archive(&state, Some(&token), query.method()).await
+}
+
+/// Tudo o que está hospedado: arquivos soltos e o conteúdo de cada pasta
+/// inteira, sob o nome dela.
+async fn serve_full_archive(
+    state: &ServerState,
+    token: Option<&str>,
+    method: ZipMethod,
+) -> Result<Response, StatusCode> {
+    let (entries, dirs) = {
+        let state_guard = state.manager.inner.lock();
+        if state_guard.index_token.as_deref() != token {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let now = SystemTime::now();
+        let dirs: Vec<HostedDir> = state_guard
+            .dirs
+            .iter()
+            .filter(|dir| dir.is_available(now))
+            .cloned()
+            .collect();
+        (archive_entries(&state_guard, None), dirs)
+    };
+    let extra = tauri::async_runtime::spawn_blocking(move || {
+        let mut sources = Vec::new();
+        for dir in dirs {
+            match hosted_dir::walk_files(&dir.root, &dir.root) {
+                Ok(items) => sources.extend(
+                    items
+                        .into_iter()
+                        .map(|item| dir_zip_source(item, &format!("{}/", dir.name))),
+                ),
+                Err(error) => {
+                    tracing::warn!(?error, root = %dir.root.display(), "hosted_dir_walk_failed")
+                }
+            }
+        }
+        sources
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    serve_archive(state, entries, extra, "fluxshare.zip", method)
+}
+
+async fn dir_archive_root_handler(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<Response, StatusCode> {
+    serve_dir_archive(&state, &id, "", query.method()).await
+}
+
+async fn dir_archive_handler(
+    State(state): State<ServerState>,
+    Path((id, path)): Path<(String, String)>,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<Response, StatusCode> {
+    serve_dir_archive(&state, &id, &path, query.method()).await
+}
+
+async fn serve_dir_archive(
+    state: &ServerState,
+    id: &str,
+    relative: &str,
+    method: ZipMethod,
+) -> Result<Response, StatusCode> {
+    let (dir, _) = find_dir(state, id)?;
+    let root = dir.root.clone();
+    let target = relative.to_string();
+    let items = tauri::async_runtime::spawn_blocking(move || {
+        let path = hosted_dir::resolve(&root, &target)?;
+        if !fs::metadata(&path)?.is_dir() {
+            return Err(io::Error::from(io::ErrorKind::NotFound));
+        }
+        hosted_dir::walk_files(&root, &path)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|error| browse_status(&error))?;
+
+    let name = relative
+        .split('/')
+        .rfind(|part| !part.is_empty())
+        .unwrap_or(&dir.name);
+    let sources = items
+        .into_iter()
+        .map(|item| dir_zip_source(item, ""))
+        .collect();
+    serve_archive(state, Vec::new(), sources, &format!("{name}.zip"), method)
+}
+
+fn dir_zip_source(item: DirItem, prefix: &str) -> ZipSource {
+    ZipSource {
+        name: format!("{prefix}{}", item.name),
+        path: item.path,
+        size: item.size,
+        modified: item.modified,
+    }
+}
+
+async fn folder_archive_handler(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<Response, StatusCode> {
+    let (entries, name) = {
+        let state_guard = state.manager.inner.lock();
+        let folder = state_guard
+            .folders
+            .iter()
+            .find(|folder| folder.id == id)
+            .ok_or(StatusCode::NOT_FOUND)?;
+        (
+            archive_entries(&state_guard, Some(folder)),
+            format!("{}.zip", folder.name),
+        )
+    };
+    serve_archive(&state, entries, Vec::new(), &name, query.method())
+}
+
+/// Arquivos do ZIP com o nome de cada um lá dentro. No ZIP completo, com
+/// mais de uma pasta de origem, cada arquivo vai sob o nome da sua pasta.
+fn archive_entries(
+    state: &TunnelState,
+    folder: Option<&HostedFolder>,
+) -> Vec<(HostedFile, String)> {
+    let mut entries = Vec::new();
+    for source in &state.folders {
+        if folder.is_some_and(|folder| folder.id != source.id) {
+            continue;
+        }
+        let prefix = folder.is_none() && state.folders.len() > 1;
+        for file in state
+            .files
+            .iter()
+            .filter(|file| source.file_ids.contains(&file.id))
+        {
+            let name = if prefix {
+                format!("{}/{}", source.name, file.name)
+            } else {
+                file.name.clone()
+            };
+            entries.push((file.clone(), name));
+        }
+    }
+    entries
+}
+
+fn unique_entry_name(used: &mut HashSet<String>, name: String) -> String {
+    if used.insert(name.clone()) {
+        return name;
+    }
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 && !name[dot..].contains('/') => name.split_at(dot),
+        _ => (name.as_str(), ""),
+    };
+    (2..)
+        .map(|n| format!("{stem} ({n}){extension}"))
+        .find(|candidate| used.insert(candidate.clone()))
+        .expect("sempre há um nome livre")
+}
+
+/// Cada arquivo hospedado no ZIP reserva o próprio tamanho, e gasta como no
+/// download avulso só o que de fato sai; os que já esgotaram ficam de fora.
+/// `extra` vem de pastas hospedadas, que não têm contagem.
+fn serve_archive(
+    state: &ServerState,
+    entries: Vec<(HostedFile, String)>,
+    extra: Vec<ZipSource>,
+    file_name: &str,
+    method: ZipMethod,
+) -> Result<Response, StatusCode> {
+    let now = SystemTime::now();
+    let had_files = !entries.is_empty();
+    let (mut sources, mut reservations): (Vec<ZipSource>, Vec<Option<Reservation>>) = entries
+        .into_iter()
+        .filter_map(|(file, name)| {
+            let reservation = file.reserve(now, file.size)?;
+            let source = ZipSource {
+                name,
+                path: file.path,
+                size: file.size,
+                modified: file.modified,
+            };
+            Some((source, Some(reservation)))
+        })
+        .unzip();
+    if had_files && sources.is_empty() && extra.is_empty() {
+        return Err(StatusCode::GONE);
+    }
+    sources.extend(extra);
+    let mut used = HashSet::new();
+    for source in &mut sources {
+        source.name = unique_entry_name(&mut used, std::mem::take(&mut source.name));
+    }
+
+    let plan = ZipPlan::new(sources, method);
+    let content_length = plan.content_length();
+    // as reservas vivem no stream: ZIP interrompido devolve o que não saiu
+    let stream = plan.into_stream(move |entry, bytes| {
+        if let Some(Some(reservation)) = reservations.get_mut(entry) {
+            reservation.sent(bytes);
+        }
+    });
+    let mut response = Response::new(throttled_body(&state.manager, stream));
+    let headers = response.headers_mut();
+    if let Some(length) = content_length {
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+    }
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    insert_attachment(headers, file_name);
+    Ok(response)
+}
+
+fn insert_attachment(headers: &mut HeaderMap, name: &str) {
+    let ascii_name = ascii_filename_fallback(name);
+    let encoded_name = utf8_percent_encode(name, FILENAME_ENCODE_SET).to_string();
+    let disposition =
+        format!("attachment; filename=\"{ascii_name}\"; filename*=UTF-8''{encoded_name}");
+    if let Ok(value) = HeaderValue::from_str(&disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+}
+
+fn build_router(server_state: ServerState) -> Router {
+    // `route_layer` só cobre as rotas declaradas antes dele
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/s/:token", get(session_index_handler))
+        .route("/s/:token/", get(session_index_handler))
+        .route("/s/:token/all.zip", get(session_archive_handler))
+        .route("/download/all.zip", get(archive_handler))
+        .route("/download/folder/:id", get(folder_archive_handler))
+        .route("/download/:id", get(download_handler))
+        .route("/download/:id/outboard", get(outboard_handler))
+        .route("/browse/:id", get(browse_root_handler))
+        .route("/browse/:id/", get(browse_root_handler))
+        .route("/browse/:id/*path", get(browse_handler))
+        .route("/zip/:id", get(dir_archive_root_handler))
+        .route("/zip/:id/", get(dir_archive_root_handler))
+        .route("/zip/:id/*path", get(dir_archive_handler))
+        .route_layer(middleware::from_fn_with_state(
+            server_state.clone(),
+            require_access,
+        ))
+        .route("/login", post(login_handler))
+        .route("/health", get(|| async { Html("ok") }))
+        .with_state(server_state)
+}
+
 async fn ensure_http_server(manager: &TunnelManager) -> Result<u16, String> {
     {
         let mut state = manager.inner.lock();
         cleanup_finished(&mut state);
         if let Some(port) = state.server_port {
-            if let Some(handle) = &state.server_handle {
+            if let Some(_handle) = &state.server_handle {
                 if true {
                     return Ok(port);
                 }
@@ -495,13 +1488,9 @@ async fn ensure_http_server(manager: &TunnelManager) -> Result<u16, String> {
             }
         };
 
-        let router = Router::new()
-            .route("/", get(index_handler))
-            .route("/download/:id", get(download_handler))
-            .route("/health", get(|| async { Html("ok") }))
-            .with_state(ServerState {
-                manager: server_manager.clone(),
-            });
+        let router = build_router(ServerState {
+            manager: server_manager.clone(),
+        });
 
         if ready_tx.send(Ok(port)).is_err() {
             return;
@@ -536,7 +1525,7 @@ fn spawn_log_reader<R: BufRead + Send + 'static>(
     url_sender: std::sync::mpsc::Sender<String>,
 ) -> ThreadJoinHandle<()> {
     std::thread::spawn(move || {
-        for line in reader.lines().flatten() {
+        for line in reader.lines().map_while(Result::ok) {
             let formatted = format!("[{source}] {line}");
             emit_log(&app, &formatted);
             if let Some(url) = extract_url(&line) {
@@ -677,7 +1666,10 @@ async fn stop_all(app: &tauri::AppHandle, manager: &TunnelManager) -> Result<(),
         state.url = None;
         state.server_port = None;
         state.files.clear();
-        state.next_file_id = 0;
+        state.folders.clear();
+        state.dirs.clear();
+        state.index_token = None;
+        state.access = None;
         (
             status,
             state.log_handles.drain(..).collect::<Vec<_>>(),
@@ -710,12 +1702,113 @@ async fn stop_all(app: &tauri::AppHandle, manager: &TunnelManager) -> Result<(),
     Ok(())
 }
 
+fn add_to_folder(folders: &mut Vec<HostedFolder>, path: &FsPath, file_id: &str) {
+    let parent = path.parent().unwrap_or(FsPath::new(""));
+    let name = parent
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "arquivos".to_string());
+    // pastas diferentes com o mesmo nome continuam separadas
+    match folders.iter_mut().find(|folder| folder.dir == parent) {
+        Some(folder) => folder.file_ids.push(file_id.to_string()),
+        None => folders.push(HostedFolder {
+            id: random_token(),
+            dir: parent.to_path_buf(),
+            name,
+            file_ids: vec![file_id.to_string()],
+        }),
+    }
+}
+
+/// Encerra a hospedagem quando nenhum arquivo pode mais ser baixado e o
+/// último download em curso terminou.
+fn spawn_expiry_watch(app: tauri::AppHandle, manager: TunnelManager, generation: u64) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            sleep(EXPIRY_POLL_INTERVAL).await;
+            let exhausted = {
+                let state = manager.inner.lock();
+                if state.host_generation != generation
+                    || (state.files.is_empty() && state.dirs.is_empty())
+                {
+                    return;
+                }
+                let now = SystemTime::now();
+                !state.files.iter().any(|file| file.is_available(now))
+                    && !state.dirs.iter().any(|dir| dir.is_available(now))
+            };
+            if exhausted && manager.active_downloads.load(Ordering::SeqCst) == 0 {
+                tracing::info!(generation, "host_files_expired");
+                emit_log(&app, "Todos os arquivos expiraram; hospedagem encerrada.");
+                if let Err(error) = stop_all(&app, &manager).await {
+                    tracing::warn!(%error, "host_expiry_stop_failed");
+                }
+                return;
+            }
+        }
+    });
+}
+
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct HostSessionInfo {
     pub local_url: String,
     pub public_url: Option<String>,
     pub files: Vec<HostedFileSummary>,
+    pub directories: Vec<HostedDirSummary>,
+    /// Link direto de cada arquivo, para compartilhar sem expor a listagem.
+    pub links: Vec<HostedLink>,
+    /// Link da listagem de cada pasta hospedada.
+    pub directory_links: Vec<HostedLink>,
+    pub protected: bool,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HostedLink {
+    pub id: String,
+    pub name: String,
+    pub local_url: String,
+    pub public_url: Option<String>,
+}
+
+impl HostedLink {
+    fn new(id: &str, name: &str, path: &str, local_root: &str, public_url: Option<&str>) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            local_url: join_url(local_root, path),
+            public_url: public_url.map(|base| join_url(base, path)),
+        }
+    }
+}
+
+enum HostTarget {
+    File {
+        path: PathBuf,
+        name: String,
+        size: u64,
+        modified: Option<SystemTime>,
+    },
+    Dir {
+        root: PathBuf,
+        name: String,
+    },
+}
+
+fn join_url(base: &str, path: &str) -> String {
+    format!("{}{path}", base.trim_end_matches('/'))
+}
+
+/// Como a sessão de host vai ser exposta; tudo opcional.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HostOptions {
+    pub cf_mode: Option<String>,
+    pub password: Option<String>,
+    pub private_index: bool,
+    pub expires_in_secs: Option<u64>,
+    pub max_downloads: Option<u32>,
 }
 
 #[tauri::command]
@@ -723,8 +1816,15 @@ pub async fn start_host(
     app: tauri::AppHandle,
     manager: tauri::State<'_, TunnelManager>,
     files: Vec<String>,
-    cf_mode: Option<String>,
+    options: Option<HostOptions>,
 ) -> Result<HostSessionInfo, String> {
+    let HostOptions {
+        cf_mode,
+        password,
+        private_index,
+        expires_in_secs,
+        max_downloads,
+    } = options.unwrap_or_default();
     if files.is_empty() {
         return Err("no files provided".to_string());
     }
@@ -738,39 +1838,110 @@ pub async fn start_host(
             }
             let metadata = fs::metadata(&path)
                 .map_err(|error| format!("falha ao ler arquivo {raw}: {error}"))?;
-            if !metadata.is_file() {
-                return Err(format!("caminho não é um arquivo: {raw}"));
-            }
             let name = path
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string())
                 .unwrap_or_else(|| raw.clone());
-            Ok((path, name, metadata.len()))
+            if metadata.is_dir() {
+                // a raiz canônica é a referência do confinamento
+                let root = fs::canonicalize(&path)
+                    .map_err(|error| format!("falha ao resolver pasta {raw}: {error}"))?;
+                let name = root
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or(name);
+                return Ok(HostTarget::Dir { root, name });
+            }
+            if !metadata.is_file() {
+                return Err(format!("caminho não é arquivo nem pasta: {raw}"));
+            }
+            Ok(HostTarget::File {
+                path,
+                name,
+                size: metadata.len(),
+                modified: metadata.modified().ok(),
+            })
         })
         .collect::<Result<Vec<_>, String>>()?;
+    // o conteúdo de uma pasta muda e sai também por ZIP: não há o que contar
+    if max_downloads.is_some()
+        && prepared
+            .iter()
+            .any(|target| matches!(target, HostTarget::Dir { .. }))
+    {
+        return Err("limite de downloads não vale para pastas; use a validade".to_string());
+    }
 
-    let summaries = {
+    // senha nova, segredo novo: cookies de sessões anteriores param de valer
+    let access = password
+        .as_deref()
+        .filter(|password| !password.is_empty())
+        .map(HostAccess::new);
+    let protected = access.is_some();
+    let index_token = private_index.then(random_token);
+    let expires_at = expires_in_secs
+        .map(|secs| {
+            SystemTime::now()
+                .checked_add(Duration::from_secs(secs))
+                .ok_or_else(|| format!("validade fora do limite: {secs} s"))
+        })
+        .transpose()?;
+    let limited = expires_at.is_some() || max_downloads.is_some();
+    let (summaries, dir_summaries, index_path, generation) = {
         let mut state = manager.inner.lock();
         cleanup_finished(&mut state);
         state.files.clear();
-        state.next_file_id = 0;
+        state.folders.clear();
+        state.dirs.clear();
+        state.access = access;
+        state.index_token = index_token;
+        state.host_generation += 1;
         let mut stored = Vec::with_capacity(prepared.len());
-        for (path, name, size) in prepared {
-            let id = state.next_file_id;
-            state.next_file_id += 1;
+        for target in prepared {
+            let (path, name, size, modified) = match target {
+                HostTarget::File {
+                    path,
+                    name,
+                    size,
+                    modified,
+                } => (path, name, size, modified),
+                HostTarget::Dir { root, name } => {
+                    state.dirs.push(HostedDir {
+                        id: random_token(),
+                        root,
+                        name,
+                        expires_at,
+                    });
+                    continue;
+                }
+            };
+            let id = random_token();
+            add_to_folder(&mut state.folders, &path, &id);
+            let outboard = Arc::new(OnceLock::new());
+            spawn_outboard(path.clone(), size, outboard.clone());
             stored.push(HostedFile {
                 id,
                 path,
                 name,
                 size,
+                modified,
+                outboard,
+                expires_at,
+                max_downloads,
+                served: Arc::new(AtomicU64::new(0)),
             });
         }
         state.files = stored;
-        summarize_files(&state.files)
+        (
+            summarize_files(&state.files),
+            summarize_dirs(&state.dirs),
+            state.index_path(),
+            state.host_generation,
+        )
     };
 
     let port = ensure_http_server(&manager).await?;
-    let local_url = format!("http://127.0.0.1:{port}/");
+    let local_root = format!("http://127.0.0.1:{port}");
 
     if summaries.is_empty() {
         emit_log(&app, "Hosted 0 files.");
@@ -782,6 +1953,17 @@ pub async fn start_host(
             .join(", ");
         emit_log(&app, &format!("Hosted {} files: {names}", summaries.len()));
     }
+    if !dir_summaries.is_empty() {
+        let names = dir_summaries
+            .iter()
+            .map(|dir| dir.name.clone())
+            .collect::<Vec<_>>()
+            .join(", ");
+        emit_log(
+            &app,
+            &format!("Hosted {} folders: {names}", dir_summaries.len()),
+        );
+    }
 
     let wants_tunnel = cf_mode
         .as_deref()
@@ -795,10 +1977,30 @@ pub async fn start_host(
         state.url.clone()
     };
 
+    if limited {
+        spawn_expiry_watch(app.clone(), manager.inner().clone(), generation);
+    }
+
+    let public_root = public_url.as_deref();
+    let links = summaries
+        .iter()
+        .map(|file| HostedLink::new(&file.id, &file.name, &file.path, &local_root, public_root))
+        .collect();
+    let directory_links = dir_summaries
+        .iter()
+        .map(|dir| HostedLink::new(&dir.id, &dir.name, &dir.path, &local_root, public_root))
+        .collect();
+
     Ok(HostSessionInfo {
-        local_url,
-        public_url,
+        local_url: join_url(&local_root, &index_path),
+        public_url: public_url
+            .as_deref()
+            .map(|base| join_url(base, &index_path)),
         files: summaries,
+        directories: dir_summaries,
+        links,
+        directory_links,
+        protected,
     })
 }
 
@@ -848,5 +2050,400 @@ pub async fn tunnel_status(
         url: state.url.clone(),
         local_port: state.server_port,
         hosted_files: files,
+        hosted_dirs: summarize_dirs(&state.dirs),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosted(expires_at: Option<SystemTime>, max_downloads: Option<u32>) -> HostedFile {
+        HostedFile {
+            id: random_token(),
+            path: PathBuf::from("arquivo.bin"),
+            name: "arquivo.bin".to_string(),
+            size: 1,
+            modified: None,
+            outboard: Arc::new(OnceLock::new()),
+            expires_at,
+            max_downloads,
+            served: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    #[test]
+    fn hosted_file_runs_out_by_count_or_time() {
+        let download = |file: &HostedFile, now| {
+            file.reserve(now, file.size)
+                .map(|mut reservation| reservation.sent(file.size))
+                .is_some()
+        };
+        let now = SystemTime::now();
+        let file = hosted(None, Some(2));
+        let clone = file.clone();
+        assert!(download(&file, now));
+        assert!(download(&clone, now));
+        assert!(!download(&file, now));
+        assert!(!clone.is_available(now));
+
+        let file = hosted(Some(now + Duration::from_secs(60)), None);
+        assert!(download(&file, now));
+        assert!(file.is_available(now));
+        let later = now + Duration::from_secs(61);
+        assert!(!file.is_available(later));
+        assert!(!download(&file, later));
+        assert!(summarize_files(&[hosted(Some(now), None)])[0].expired);
+
+        // trechos gastam o que entregam; o que não saiu volta ao saldo
+        let file = HostedFile {
+            size: 10,
+            ..hosted(None, Some(1))
+        };
+        let mut first = file.reserve(now, 5).unwrap();
+        first.sent(5);
+        let mut second = file.reserve(now, 5).unwrap();
+        assert!(file.reserve(now, 1).is_none());
+        assert_eq!(file.downloads(), 1);
+        drop(first);
+        second.sent(2);
+        drop(second);
+        assert!(file.is_available(now));
+        assert!(file.reserve(now, 4).is_none());
+        let mut rest = file.reserve(now, 3).unwrap();
+        rest.sent(3);
+        drop(rest);
+        assert!(!file.is_available(now));
+    }
+
+    #[test]
+    fn zip_routes_sit_beside_file_downloads() {
+        // o roteador entra em pânico na montagem se as rotas conflitarem
+        let _ = build_router(ServerState {
+            manager: TunnelManager::default(),
+        });
+
+        let mut used = HashSet::new();
+        assert_eq!(unique_entry_name(&mut used, "a.txt".into()), "a.txt");
+        assert_eq!(unique_entry_name(&mut used, "a.txt".into()), "a (2).txt");
+        assert_eq!(unique_entry_name(&mut used, "a.txt".into()), "a (3).txt");
+        assert_eq!(unique_entry_name(&mut used, "LEIAME".into()), "LEIAME");
+        assert_eq!(unique_entry_name(&mut used, "LEIAME".into()), "LEIAME (2)");
+    }
+
+    async fn serve(manager: TunnelManager) -> u16 {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let router = build_router(ServerState { manager });
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        port
+    }
+
+    async fn get(port: u16, path: &str) -> (u16, String) {
+        send(port, "GET", path, &[], "").await
+    }
+
+    async fn send(
+        port: u16,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> (u16, String) {
+        use tokio::io::AsyncWriteExt;
+        let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .unwrap();
+        let mut request =
+            format!("{method} {path} HTTP/1.1\r\nHost: teste\r\nConnection: close\r\n");
+        for (name, value) in headers {
+            let _ = write!(request, "{name}: {value}\r\n");
+        }
+        let _ = write!(request, "Content-Length: {}\r\n\r\n{body}", body.len());
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8_lossy(&response).to_string();
+        let status = response[9..12].parse().unwrap();
+        (status, response)
+    }
+
+    #[test]
+    fn hosted_directory_is_browsable_but_confined() {
+        let base = tempfile::tempdir().unwrap();
+        let root = base.path().join("projeto");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("docs/notas.txt"), b"conteudo das notas").unwrap();
+        fs::write(base.path().join("segredo.txt"), b"nao sai").unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(base.path().join("segredo.txt"), root.join("fuga")).unwrap();
+            std::os::unix::fs::symlink(base.path(), root.join("docs/acima")).unwrap();
+        }
+
+        let manager = TunnelManager::default();
+        manager.inner.lock().dirs.push(HostedDir {
+            id: "pasta".to_string(),
+            root: fs::canonicalize(&root).unwrap(),
+            name: "projeto".to_string(),
+            expires_at: None,
+        });
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let port = serve(manager.clone()).await;
+
+            let (status, body) = get(port, "/browse/pasta/").await;
+            assert_eq!(status, 200);
+            assert!(!body.contains("fuga"));
+            assert!(body.contains("href=\"/browse/pasta/docs/\""));
+            assert!(body.contains("href=\"/zip/pasta/\""));
+
+            let (status, body) = get(port, "/browse/pasta/docs/").await;
+            assert_eq!(status, 200);
+            // trilha: início, raiz como link, pasta atual como texto
+            assert!(body.contains("<a href=\"/\">Início</a>"));
+            assert!(body.contains("<a href=\"/browse/pasta/\">projeto</a>"));
+            assert!(body.contains("<span>docs</span>"));
+
+            let (status, body) = get(port, "/browse/pasta/docs/notas.txt").await;
+            assert_eq!(status, 200);
+            assert!(body.ends_with("conteudo das notas"));
+
+            for escape in [
+                "/browse/pasta/../segredo.txt",
+                "/browse/pasta/%2E%2E/segredo.txt",
+                "/browse/pasta/docs/..%2F..%2Fsegredo.txt",
+                "/zip/pasta/%2E%2E",
+                "/browse/outra/",
+                "/browse/pasta/fuga",
+                "/browse/pasta/docs/acima/segredo.txt",
+                "/zip/pasta/docs/acima",
+            ] {
+                let (status, body) = get(port, escape).await;
+                assert_eq!(status, 404, "{escape}");
+                assert!(!body.contains("nao sai"), "{escape}");
+            }
+
+            let (status, body) = get(port, "/zip/pasta/docs").await;
+            assert_eq!(status, 200);
+            assert!(body.contains("filename=\"docs.zip\""));
+            assert!(body.contains("notas.txt"));
+            assert!(!body.contains("nao sai"));
+            let (_, body) = get(port, "/zip/pasta/").await;
+            assert!(body.contains("notas.txt"));
+            assert!(!body.contains("nao sai"));
+
+            // com link de sessão, a trilha não entrega o token
+            manager.inner.lock().index_token = Some("t0k3n".to_string());
+            let (status, body) = get(port, "/browse/pasta/docs/").await;
+            assert_eq!(status, 200);
+            assert!(!body.contains("Início"));
+            assert!(!body.contains("t0k3n"));
+            assert!(body.contains("<nav class=\"crumbs\"><a href=\"/browse/pasta/\">projeto</a>"));
+        });
+    }
+
+    #[test]
+    fn password_gates_everything_but_health_and_login() {
+        let base = tempfile::tempdir().unwrap();
+        let path = base.path().join("arquivo.bin");
+        fs::write(&path, b"conteudo protegido").unwrap();
+        let file = HostedFile {
+            path,
+            size: 18,
+            ..hosted(None, None)
+        };
+        let download = format!("/download/{}", file.id);
+
+        let manager = TunnelManager::default();
+        {
+            let mut state = manager.inner.lock();
+            state.files.push(file);
+            state.access = Some(HostAccess::new("s3nha"));
+        }
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let port = serve(manager).await;
+
+            let (status, body) = get(port, "/").await;
+            assert_eq!(status, 401);
+            assert!(body.contains("name=\"password\""));
+            assert!(!body.contains("arquivo.bin"));
+            let (status, body) = get(port, &download).await;
+            assert_eq!(status, 401);
+            assert!(!body.contains("conteudo protegido"));
+            assert_eq!(get(port, "/health").await.0, 200);
+
+            let form = [("Content-Type", "application/x-www-form-urlencoded")];
+            let (status, _) = send(port, "POST", "/login", &form, "password=errada&next=/").await;
+            assert_eq!(status, 401);
+
+            let over_tunnel = [
+                form[0],
+                ("X-Forwarded-Proto", "https"),
+                ("CF-Connecting-IP", "203.0.113.7"),
+            ];
+            let (status, response) = send(
+                port,
+                "POST",
+                "/login",
+                &over_tunnel,
+                "password=s3nha&next=/",
+            )
+            .await;
+            assert_eq!(status, 303);
+            let set_cookie = response
+                .lines()
+                .find_map(|line| line.strip_prefix("set-cookie: "))
+                .expect("set-cookie");
+            assert!(set_cookie.ends_with("; Secure"));
+            let cookie = set_cookie.split(';').next().unwrap();
+
+            let with_cookie = [("Cookie", cookie)];
+            let (status, body) = send(port, "GET", "/", &with_cookie, "").await;
+            assert_eq!(status, 200);
+            assert!(body.contains("arquivo.bin"));
+            let (status, body) = send(port, "GET", &download, &with_cookie, "").await;
+            assert_eq!(status, 200);
+            assert!(body.ends_with("conteudo protegido"));
+
+            // cada IP tem a sua espera; errar demais trava até a senha certa
+            let guesser = [form[0], ("CF-Connecting-IP", "198.51.100.9")];
+            let mut statuses = Vec::new();
+            for _ in 0..6 {
+                let body = "password=chute&next=/";
+                statuses.push(send(port, "POST", "/login", &guesser, body).await.0);
+            }
+            assert_eq!(statuses.iter().filter(|status| **status == 429).count(), 2);
+            let (status, response) =
+                send(port, "POST", "/login", &guesser, "password=s3nha&next=/").await;
+            assert_eq!(status, 429);
+            assert!(response.contains("retry-after: "));
+            let (status, _) = send(
+                port,
+                "POST",
+                "/login",
+                &over_tunnel,
+                "password=s3nha&next=/",
+            )
+            .await;
+            assert_eq!(status, 303);
+        });
+    }
+
+    #[test]
+    fn private_index_lives_only_behind_its_token() {
+        let manager = TunnelManager::default();
+        {
+            let mut state = manager.inner.lock();
+            state.files.push(hosted(None, None));
+            state.index_token = Some("t0k3n".to_string());
+        }
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let port = serve(manager).await;
+
+            for hidden in ["/", "/download/all.zip", "/s/errado/", "/s/errado/all.zip"] {
+                let (status, body) = get(port, hidden).await;
+                assert_eq!(status, 404, "{hidden}");
+                assert!(!body.contains("arquivo.bin"), "{hidden}");
+            }
+
+            for listing in ["/s/t0k3n/", "/s/t0k3n"] {
+                let (status, body) = get(port, listing).await;
+                assert_eq!(status, 200, "{listing}");
+                assert!(body.contains("arquivo.bin"), "{listing}");
+                assert!(body.contains("href=\"/s/t0k3n/all.zip\""), "{listing}");
+            }
+        });
+    }
+
+    #[test]
+    fn ranged_requests_count_toward_the_download_limit() {
+        let base = tempfile::tempdir().unwrap();
+        let path = base.path().join("arquivo.bin");
+        fs::write(&path, b"0123456789").unwrap();
+        let file = HostedFile {
+            path,
+            size: 10,
+            ..hosted(None, Some(1))
+        };
+        let download = format!("/download/{}", file.id);
+        let manager = TunnelManager::default();
+        manager.inner.lock().files.push(file);
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let port = serve(manager).await;
+            let (status, body) = send(port, "GET", &download, &[("Range", "bytes=5-")], "").await;
+            assert_eq!(status, 206);
+            assert!(body.ends_with("56789"));
+            let (status, body) = send(port, "GET", &download, &[("Range", "bytes=0-4")], "").await;
+            assert_eq!(status, 206);
+            assert!(body.ends_with("01234"));
+
+            // o arquivo inteiro já saiu em trechos: nem trecho nem download novo
+            for range in ["bytes=5-", "bytes=0-0"] {
+                let (status, _) = send(port, "GET", &download, &[("Range", range)], "").await;
+                assert_eq!(status, 410, "{range}");
+            }
+            assert_eq!(get(port, &download).await.0, 410);
+        });
+    }
+
+    #[test]
+    fn zip_entries_charge_only_what_they_send() {
+        let base = tempfile::tempdir().unwrap();
+        let path = base.path().join("grande.bin");
+        let size = 1024 * 1024;
+        fs::write(&path, vec![9u8; size]).unwrap();
+        let file = HostedFile {
+            path,
+            name: "grande.bin".to_string(),
+            size: size as u64,
+            ..hosted(None, Some(1))
+        };
+        let state = ServerState {
+            manager: TunnelManager::default(),
+        };
+        let zip = |file: &HostedFile| {
+            let entries = vec![(file.clone(), file.name.clone())];
+            serve_archive(&state, entries, Vec::new(), "tudo.zip", ZipMethod::Store)
+        };
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mut body = zip(&file).unwrap().into_body().into_data_stream();
+            // cabeçalho local e o primeiro bloco de dados; o cliente some aqui
+            body.next().await.unwrap().unwrap();
+            body.next().await.unwrap().unwrap();
+            assert!(!file.is_available(SystemTime::now()));
+            drop(body);
+
+            let served = file.served.load(Ordering::SeqCst);
+            assert!(served > 0 && served < size as u64, "{served}");
+            assert!(file.is_available(SystemTime::now()));
+            // o resto do saldo não paga outro ZIP inteiro
+            assert_eq!(zip(&file).unwrap_err(), StatusCode::GONE);
+        });
+
+        // ZIP que vai até o fim gasta o download inteiro
+        let file = HostedFile {
+            served: Arc::new(AtomicU64::new(0)),
+            ..file
+        };
+        rt.block_on(async {
+            let mut body = zip(&file).unwrap().into_body().into_data_stream();
+            while let Some(block) = body.next().await {
+                block.unwrap();
+            }
+        });
+        assert_eq!(file.served.load(Ordering::SeqCst), size as u64);
+        assert!(!file.is_available(SystemTime::now()));
+    }
+}
@@ -1,17 +1,35 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use bytes::Bytes;
 use parking_lot::Mutex;
 use serde::Serialize;
+use tokio::sync::{mpsc, watch};
 use webrtc::api::media_engine::MediaEngine;
 use webrtc::api::APIBuilder;
 use webrtc::data_channel::data_channel_init::RTCDataChannelInit;
 use webrtc::peer_connection::configuration::RTCConfiguration;
 use webrtc::peer_connection::RTCPeerConnection;
 
+use super::transport::WebRtcChannel;
+
 #[derive(Default, Clone)]
 pub struct WebRTCManager {
     connections: Arc<Mutex<HashMap<String, Arc<RTCPeerConnection>>>>,
+    channels: Arc<Mutex<HashMap<String, Arc<WebRtcChannel>>>>,
+}
+
+impl WebRTCManager {
+    pub fn channel(&self, self_id: &str, target_id: &str) -> Option<Arc<WebRtcChannel>> {
+        self.channels
+            .lock()
+            .get(&peer_key(self_id, target_id))
+            .cloned()
+    }
+}
+
+fn peer_key(self_id: &str, target_id: &str) -> String {
+    format!("{self_id}->{target_id}")
 }
 
 #[derive(Serialize)]
@@ -59,6 +77,8 @@ pub async fn webrtc_start(
     let self_id_open = self_id.clone();
     let target_id_open = target_id.clone();
     let signaling_open = signaling_url.clone();
+    let (open_tx, open_rx) = watch::channel(false);
+    let (incoming_tx, incoming_rx) = mpsc::unbounded_channel::<Bytes>();
 
     data_channel.on_open(Box::new(move || {
         tracing::info!(
@@ -68,19 +88,25 @@ pub async fn webrtc_start(
             signaling_url = %signaling_open,
             "datachannel_open"
         );
+        let _ = open_tx.send(true);
         Box::pin(async {})
     }));
 
     data_channel.on_message(Box::new(move |msg| {
         tracing::debug!(len = msg.data.len(), "datachannel_message");
+        let _ = incoming_tx.send(msg.data);
         Box::pin(async {})
     }));
 
     // aqui fora usamos os originais (não movidos)
-    webrtc_manager
-        .connections
-        .lock()
-        .insert(format!("{}->{}", self_id, target_id), pc.into());
+    let key = peer_key(&self_id, &target_id);
+    let channel = Arc::new(WebRtcChannel::new(
+        data_channel.clone(),
+        open_rx,
+        incoming_rx,
+    ));
+    webrtc_manager.channels.lock().insert(key.clone(), channel);
+    webrtc_manager.connections.lock().insert(key, pc.into());
 
     Ok(())
 }
@@ -1,19 +1,38 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod commands {
+    pub mod archive;
+    pub mod bandwidth;
+    pub mod chunking;
+    pub mod compression;
+    pub mod crypto;
     pub mod files;
+    pub mod history;
+    pub mod host_access;
+    pub mod hosted_dir;
+    pub mod manifest;
+    pub mod outboard;
     pub mod quic;
+    pub mod receive;
+    pub mod scheduler;
     pub mod settings;
     pub mod transfer;
+    pub mod transport;
     pub mod tunnel;
     pub mod webrtc;
 }
 
 use commands::{
+    bandwidth::{set_session_rate_limit, BandwidthManager},
     files::{list_files, read_file_range, write_file_range},
+    history::{apply_retention, delete_transfer, list_transfers, HistoryManager},
     quic::{quic_start, QuicManager},
+    receive::receive_files,
     settings::{get_settings, set_settings, SettingsManager},
-    transfer::{get_status, send_files, TransferManager},
+    transfer::{
+        cancel_transfer, get_status, pause_transfer, reorder_transfers, resume_all,
+        resume_transfer, send_files, set_transfer_priority, TransferManager,
+    },
     tunnel::{start_host, start_tunnel, stop_host, stop_tunnel, tunnel_status, TunnelManager},
     webrtc::{start_signaling, webrtc_start, WebRTCManager},
 };
@@ -55,19 +74,33 @@ fn open_logs_folder(app: tauri::AppHandle) -> Result<(), String> {
 
 fn main() {
     init_tracing();
-    let transfer_manager = TransferManager::default();
+    let bandwidth_manager = BandwidthManager::default();
+    let transfer_manager = TransferManager::with_bandwidth(bandwidth_manager.clone());
     let settings_manager = SettingsManager::default();
-    let tunnel_manager = TunnelManager::default(); // LLM-LOCK: central manager for Cloudflare tunnel lifecycle and stop events
+    let history_manager = HistoryManager::default();
+    let tunnel_manager = TunnelManager::with_bandwidth(bandwidth_manager.clone()); // LLM-LOCK: central manager for Cloudflare tunnel lifecycle and stop events
     let webrtc_manager = WebRTCManager::default();
     let quic_manager = QuicManager::default();
 
     settings_manager
         .ensure_initialized()
         .expect("settings init");
+    if let Ok(settings) = settings_manager.get_settings() {
+        bandwidth_manager.configure(&settings);
+        transfer_manager.set_max_concurrent(settings.max_concurrent_sessions as usize);
+    }
+    apply_retention(&history_manager, &settings_manager);
+    transfer_manager.attach_history(history_manager.clone());
+    let restored = transfer_manager.restore_unfinished();
+    if restored > 0 {
+        tracing::info!(restored, "unfinished_sessions_restored");
+    }
 
     tauri::Builder::default()
         .manage(transfer_manager.clone())
         .manage(settings_manager.clone())
+        .manage(history_manager.clone())
+        .manage(bandwidth_manager.clone())
         .manage(tunnel_manager.clone())
         .manage(webrtc_manager.clone())
         .manage(quic_manager.clone())
@@ -79,17 +112,28 @@ fn main() {
             webrtc_start,
             quic_start,
             send_files,
+            receive_files,
             get_status,
+            cancel_transfer,
+            pause_transfer,
+            resume_transfer,
+            resume_all,
+            set_transfer_priority,
+            reorder_transfers,
+            list_transfers,
+            delete_transfer,
             start_host,
             start_tunnel,
             stop_host,
             stop_tunnel,
             tunnel_status,
             set_settings,
+            set_session_rate_limit,
             get_settings,
             open_logs_folder
         ])
         .setup(move |app| {
+            transfer_manager.attach(app.handle());
             app.listen_global("tauri://close-requested", move |_event| {
                 tracing::info!("shutdown requested");
             });
@@ -14,9 +14,34 @@ const MAX_SIMPLE_LOGS = 120;
 type TunnelLifecycle = "RUNNING" | "STOPPED";
 
 type HostedFileSummary = {
-  id: number;
+  id: string;
   name: string;
   size: number;
+  path: string;
+  expiresAt?: number | null;
+  maxDownloads?: number | null;
+  downloads: number;
+  expired: boolean;
+};
+
+type HostedDirSummary = {
+  id: string;
+  name: string;
+  path: string;
+  expiresAt?: number | null;
+  expired: boolean;
+};
+
+type HostLimits = {
+  expiresInSecs?: number;
+  maxDownloads?: number;
+};
+
+type HostedLink = {
+  id: string;
+  name: string;
+  localUrl: string;
+  publicUrl?: string | null;
 };
 
 type TunnelStatusPayload = {
@@ -24,6 +49,7 @@ type TunnelStatusPayload = {
   url?: string | null;
   localPort?: number | null;
   hostedFiles?: HostedFileSummary[];
+  hostedDirs?: HostedDirSummary[];
 };
 
 type TunnelLogPayload = {
@@ -42,7 +68,13 @@ export interface TunnelStoreState {
   missingBinary: boolean;
   autoStopAt: number | null;
   start(options?: StartOptions): Promise<void>;
-  host(files: string[], provider?: TunnelProvider): Promise<void>;
+  host(
+    files: string[],
+    provider?: TunnelProvider,
+    password?: string,
+    privateIndex?: boolean,
+    limits?: HostLimits,
+  ): Promise<void>;
   stop(manual?: boolean): Promise<void>;
   refresh(): Promise<void>;
   clear(): void;
@@ -73,6 +105,10 @@ type HostSessionInfo = {
   localUrl: string;
   publicUrl?: string | null;
   files: HostedFileSummary[];
+  directories: HostedDirSummary[];
+  links: HostedLink[];
+  directoryLinks: HostedLink[];
+  protected: boolean;
 };
 
 let autoStopHandle: ReturnType<typeof setTimeout> | null = null;
@@ -250,7 +286,7 @@ export const useTunnelStore = create<TunnelStoreState>((set, get) => {
         scheduleAutoStop(started ? autoStopMinutes : null);
       }
     },
-    async host(files, provider = "cloudflare") {
+    async host(files, provider = "cloudflare", password, privateIndex, limits) {
       if (!isTauri()) {
         set((state) => ({
           logs: appendLog(state.logs, "Hospedagem disponível apenas no app desktop.", MAX_ADVANCED_LOGS),
@@ -275,13 +311,29 @@ export const useTunnelStore = create<TunnelStoreState>((set, get) => {
             status: "RUNNING",
             url: state.url ?? "https://mock-tunnel.local",
             localUrl: state.localUrl ?? "http://127.0.0.1:8787/",
-            hostedFiles: files.map((name, idx) => ({ id: idx, name: name.split(/[\\/]/).pop() ?? name, size: 0 })),
+            hostedFiles: files.map((name, idx) => ({
+              id: String(idx),
+              name: name.split(/[\\/]/).pop() ?? name,
+              size: 0,
+              path: `/download/${idx}`,
+              downloads: 0,
+              expired: false,
+            })),
             logs: appendLog(state.logs, "Hospedagem mock iniciada.", MAX_ADVANCED_LOGS),
             simpleLogs: appendLog(state.simpleLogs, "Hospedagem mock ativa.", MAX_SIMPLE_LOGS),
           }));
           return;
         }
-        const response = (await invoke("start_host", { files, cfMode: "cloudflared" })) as HostSessionInfo;
+        const response = (await invoke("start_host", {
+          files,
+          options: {
+            cfMode: "cloudflared",
+            password: password || null,
+            privateIndex: privateIndex ?? false,
+            expiresInSecs: limits?.expiresInSecs ?? null,
+            maxDownloads: limits?.maxDownloads ?? null,
+          },
+        })) as HostSessionInfo;
         set((state) => ({
           loading: false,
           status: response.publicUrl ? "RUNNING" : state.status,